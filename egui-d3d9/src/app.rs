@@ -1,21 +1,65 @@
-use egui::{epaint::Primitive, Context, TextureId};
+use egui::{epaint::PaintCallbackInfo, ColorImage, Context, Event, RawInput, TextureId};
+use std::time::Instant;
+
 use windows::Win32::{
     Foundation::{HWND, LPARAM, RECT, WPARAM},
-    Graphics::Direct3D9::{IDirect3DDevice9, IDirect3DTexture9, D3DPT_TRIANGLELIST, D3DVIEWPORT9},
+    Graphics::Direct3D9::{IDirect3DDevice9, IDirect3DTexture9},
     UI::WindowsAndMessaging::GetClientRect,
 };
 
 use crate::{
     inputman::InputManager,
-    mesh::{Buffers, GpuVertex, MeshDescriptor},
-    set_clipboard_text,
-    state::DxState,
-    texman::TextureManager,
+    platform, rawinput,
+    repaint::RepaintSignal,
+    state::{RenderTarget, StateCache},
+    ClipboardProvider, EguiDx9Builder, InputBlocking, InputSink, NativeClipboard, Renderer, Result,
+    WheelConfig, WndProcResult,
 };
 
+/// the callback type this backend expects inside of an [`egui::PaintCallback`].
+///
+/// the device's viewport and scissor rect are set up for the callback's area before it is called,
+/// everything else is left as we use it for drawing egui. you're free to change any state,
+/// we re-apply ours once the callback returns.
+///
+/// ```ignore
+/// let callback = egui::PaintCallback {
+///     rect,
+///     callback: std::sync::Arc::new(egui_d3d9::CallbackFn::new(|info, dev| {
+///         // draw your stuff
+///     })),
+/// };
+/// ui.painter().add(callback);
+/// ```
+pub struct CallbackFn {
+    pub(crate) f: Box<Callback>,
+}
+
+type Callback = dyn Fn(PaintCallbackInfo, &IDirect3DDevice9) + Send + Sync;
+
+impl CallbackFn {
+    pub fn new<F: Fn(PaintCallbackInfo, &IDirect3DDevice9) + Send + Sync + 'static>(
+        callback: F,
+    ) -> Self {
+        Self {
+            f: Box::new(callback),
+        }
+    }
+}
+
 pub trait UIHandler {
     fn ui(&mut self, ctx: &Context);
 
+    /// look at or change the input before egui runs with it, e.g. to remap keys or drop events.
+    ///
+    /// called every frame we run the ui, once pastes have their text from the clipboard.
+    #[allow(unused_variables)]
+    fn prepare_input(&mut self, raw_input: &mut RawInput) {}
+
+    /// resolve a user texture that wasn't registered through [`EguiDx9::register_native_texture`].
+    ///
+    /// anything that can't be resolved is drawn with a plain white texture.
+    /// ids with the top bit set belong to registered textures, those never get here.
     #[allow(unused_variables)]
     fn resolve_user_texture(&mut self, id: u64) -> Option<&IDirect3DTexture9> {
         None
@@ -26,18 +70,16 @@ pub struct EguiDx9<H> {
     handler: H,
     hwnd: HWND,
     reactive: bool,
-    input_man: InputManager,
-    // get it? tEx-man? tax-man? no?
-    tex_man: TextureManager,
+    // everything the window procedure feeds
+    sink: InputSink,
     ctx: Context,
-    buffers: Buffers,
-    prims: Vec<MeshDescriptor>,
-    last_idx_capacity: usize,
-    last_vtx_capacity: usize,
-    should_reset: bool,
-
-    vertices: Vec<GpuVertex>,
-    indices: Vec<u32>,
+    renderer: Renderer<IDirect3DDevice9>,
+    // reactive mode: when egui wants to run again, and what it last ran with
+    repaint: RepaintSignal,
+    last_screen_size: [u32; 2],
+    // where we last asked for the ime windows to go, in pixels
+    ime_rect: Option<egui::Rect>,
+    clipboard: Box<dyn ClipboardProvider>,
 }
 
 impl<H: UIHandler> EguiDx9<H> {
@@ -46,213 +88,345 @@ impl<H: UIHandler> EguiDx9<H> {
     ///
     ///
     /// if you are using this purely as a UI, you can set `reactive` to true.
-    /// this causes us to only re-draw the menu once something changes.
+    /// this causes us to only run your ui once input arrives or egui asks for a repaint,
+    /// every other frame we just draw the last result again.
     ///
-    /// the menu doesn't always catch these changes, so only use this if you need to.
+    /// use [`EguiDx9::builder`] if you need more control.
     ///
-    /// # Panics
+    /// # Errors
     /// If buffers cannot be created
-    pub fn init(dev: &IDirect3DDevice9, hwnd: HWND, handler: H, reactive: bool) -> Self {
-        Self {
+    pub fn init(dev: &IDirect3DDevice9, hwnd: HWND, handler: H, reactive: bool) -> Result<Self> {
+        EguiDx9Builder::new()
+            .reactive(reactive)
+            .build(dev, hwnd, handler)
+    }
+
+    pub fn builder() -> EguiDx9Builder {
+        EguiDx9Builder::new()
+    }
+
+    pub(crate) fn from_builder(
+        builder: EguiDx9Builder,
+        dev: &IDirect3DDevice9,
+        hwnd: HWND,
+        handler: H,
+    ) -> Result<Self> {
+        let ctx = builder.ctx.unwrap_or_default();
+
+        if let Some(style) = builder.style {
+            ctx.set_style(style);
+        }
+
+        if let Some(fonts) = builder.fonts {
+            ctx.set_fonts(fonts);
+        }
+
+        let mut input_man = InputManager::new(hwnd);
+        input_man.set_pixels_per_point_override(builder.pixels_per_point);
+        input_man.set_ui_scale(builder.ui_scale);
+        input_man.set_wheel_config(builder.wheel);
+
+        if builder.drag_and_drop {
+            platform::accept_files(hwnd, true);
+            input_man.set_accept_files(true);
+        }
+
+        let renderer = Renderer::new(
+            dev,
+            StateCache::new(builder.render_target),
+            builder.vertex_capacity,
+            builder.index_capacity,
+            builder.sampler,
+        )?;
+
+        let sink = InputSink::new(input_man, builder.input_blocking, ctx.clone());
+        sink.lock().raw_input_request = Some(builder.raw_input);
+
+        Ok(Self {
             handler,
             hwnd,
-            reactive,
-            tex_man: TextureManager::new(),
-            input_man: InputManager::new(hwnd),
-            ctx: Context::default(),
-            buffers: Buffers::create_buffers(dev, 16384, 16384).expect("buffers"),
-            prims: Vec::new(),
-            last_idx_capacity: 0,
-            last_vtx_capacity: 0,
-            should_reset: false,
-            vertices: Vec::new(),
-            indices: Vec::new(),
-        }
+            reactive: builder.reactive,
+            sink,
+            repaint: RepaintSignal::install(&ctx),
+            ctx,
+            renderer,
+            last_screen_size: [0, 0],
+            ime_rect: None,
+            clipboard: builder
+                .clipboard
+                .unwrap_or_else(|| Box::new(NativeClipboard)),
+        })
     }
 
-    pub fn pre_reset(&mut self) {
-        self.buffers.delete_buffers();
-        self.tex_man.deallocate_textures();
+    /// the context we run the ui with.
+    pub fn context(&self) -> &Context {
+        &self.ctx
+    }
+
+    /// scale the whole ui by this factor, on top of the window's dpi.
+    pub fn set_ui_scale(&mut self, scale: f32) {
+        self.sink.lock().input_man.set_ui_scale(scale);
+    }
+
+    pub fn ui_scale(&self) -> f32 {
+        self.sink.lock().input_man.ui_scale()
+    }
+
+    /// how the mouse wheel scrolls and zooms.
+    pub fn set_wheel_config(&mut self, config: WheelConfig) {
+        self.sink.lock().input_man.set_wheel_config(config);
+    }
+
+    pub fn wheel_config(&self) -> WheelConfig {
+        *self.sink.lock().input_man.wheel_config()
+    }
+
+    /// a handle for your window procedure, for when it doesn't run on the thread calling [`EguiDx9::present`].
+    ///
+    /// it feeds the same input as [`EguiDx9::wnd_proc`], whatever arrives is drained on the next `present`.
+    pub fn input_sink(&self) -> InputSink {
+        self.sink.clone()
+    }
 
-        self.should_reset = true;
+    /// make a texture of your own available to egui, e.g. for [`egui::Image`].
+    ///
+    /// the texture stays yours, we only hold a reference to it.
+    /// [`EguiDx9::pre_reset`] lets go of that reference, so hand the texture (or a re-created one,
+    /// if it lives in `D3DPOOL_DEFAULT`) back through [`EguiDx9::replace_native_texture`] after the reset.
+    /// until then, the id is drawn with a plain white texture.
+    ///
+    /// the id has its top bit set, so it never shadows one you resolve in [`UIHandler::resolve_user_texture`].
+    pub fn register_native_texture(&mut self, texture: IDirect3DTexture9) -> TextureId {
+        self.renderer.register_native_texture(texture)
     }
 
-    /// # Panics
+    /// swap the texture behind an id from [`EguiDx9::register_native_texture`].
+    ///
     /// # Errors
-    /// underlying render error
-    pub fn present(&mut self, dev: &IDirect3DDevice9) -> windows::core::Result<()> {
-        if unsafe { dev.TestCooperativeLevel() }.is_err() {
-            return Ok(());
-        }
+    /// if the id was never registered, or has been freed already.
+    pub fn replace_native_texture(
+        &mut self,
+        id: TextureId,
+        texture: IDirect3DTexture9,
+    ) -> Result<()> {
+        self.renderer.replace_native_texture(id, texture)
+    }
+
+    /// stop drawing a registered texture and give it back.
+    /// anything still using the id is drawn with a plain white texture.
+    ///
+    /// `None` if the id isn't one of ours, or we let go of the texture in [`EguiDx9::pre_reset`] and never got it back.
+    pub fn free_native_texture(&mut self, id: TextureId) -> Option<IDirect3DTexture9> {
+        self.renderer.free_native_texture(id)
+    }
+
+    /// call before `IDirect3DDevice9::Reset`. we release everything that wouldn't survive it,
+    /// including our references to your registered textures.
+    pub fn pre_reset(&mut self) {
+        self.renderer.pre_reset();
+    }
+
+    /// # Errors
+    /// [`Error::DeviceLost`](crate::Error::DeviceLost) while the device is lost, nothing is drawn in that case.
+    /// otherwise, the underlying render error.
+    pub fn present(&mut self, dev: &IDirect3DDevice9) -> Result<()> {
+        self.renderer.prepare(dev)?;
 
-        if self.should_reset {
-            self.buffers = Buffers::create_buffers(dev, 16384, 16384)?;
-            self.tex_man.reallocate_textures(dev);
+        let screen_size = self.get_screen_size()?;
+
+        if self.needs_run(screen_size) {
+            self.run_frame(dev, screen_size)?;
         }
 
-        let output = self.ctx.run(self.input_man.collect_input(), |ctx| {
-            // safe. present will never run in parallel.
-            self.handler.ui(ctx);
+        let handler = &mut self.handler;
+        self.renderer.draw(dev, screen_size, |id| {
+            handler.resolve_user_texture(id).cloned()
+        })
+    }
+
+    /// in reactive mode, we only run egui if something could have changed.
+    /// otherwise we just draw what we uploaded last time.
+    fn needs_run(&self, screen_size: [u32; 2]) -> bool {
+        !self.reactive
+            || self.renderer.is_reset_pending()
+            || self.sink.lock().input_man.has_events()
+            || self.last_screen_size != screen_size
+            || self.repaint.is_due(Instant::now())
+    }
+
+    fn run_frame(&mut self, dev: &IDirect3DDevice9, screen_size: [u32; 2]) -> Result<()> {
+        // egui's lock before ours, never both. the window procedure takes them the other way around.
+        let zoom_factor = self.ctx.zoom_factor();
+        let (mut raw_input, cursor) = {
+            let mut shared = self.sink.lock();
+            let raw_input = shared.input_man.collect_input(zoom_factor);
+            let cursor = shared.input_man.virtual_cursor().filter(|_| shared.open);
+            (raw_input, cursor)
+        };
+
+        // the input manager only marks where to paste, we read the clipboard once egui needs it.
+        let clipboard = &mut self.clipboard;
+        raw_input.events.retain_mut(|event| match event {
+            Event::Paste(text) if text.is_empty() => match clipboard.get_text() {
+                Some(pasted) if !pasted.is_empty() => {
+                    *text = pasted;
+                    true
+                }
+                _ => false,
+            },
+            _ => true,
         });
 
-        if self.should_reset {
-            self.ctx.request_repaint();
-            self.should_reset = false;
-        }
+        self.handler.prepare_input(&mut raw_input);
 
-        if !output.textures_delta.is_empty() {
-            self.tex_man
-                .process_set_deltas(dev, &output.textures_delta)?;
-        }
+        // anything this run asks for comes back through the callback.
+        self.repaint.clear();
 
+        let output = self
+            .renderer
+            .run(dev, &self.ctx, raw_input, screen_size, |ctx| {
+                // safe. present will never run in parallel.
+                self.handler.ui(ctx);
+
+                // the game hides the real one.
+                if let Some(pos) = cursor {
+                    rawinput::paint_cursor(ctx, pos);
+                }
+            })?;
+
+        self.last_screen_size = screen_size;
+
+        // egui 0.30 only ever copies text, there is nothing for images in its output yet.
         if !output.platform_output.copied_text.is_empty() {
-            let _ = set_clipboard_text(output.platform_output.copied_text);
+            self.clipboard.set_text(&output.platform_output.copied_text);
         }
 
-        if output.shapes.is_empty() {
-            // early return, don't forget to free textures
-            if !output.textures_delta.is_empty() {
-                self.tex_man.process_free_deltas(&output.textures_delta);
-            }
-            return Ok(());
+        if let Some(url) = &output.platform_output.open_url {
+            platform::open_url(url);
         }
 
-        // we only need to update the buffers if we are actually changing something
-        if self.ctx.has_requested_repaint() || !self.reactive {
-            // TODO: old code added last len + 512
-            self.vertices.clear();
-            self.indices.clear();
-
-            self.prims = self
-                .ctx
-                .tessellate(output.shapes, output.pixels_per_point)
-                .into_iter()
-                .filter_map(|prim| {
-                    if let Primitive::Mesh(mesh) = prim.primitive {
-                        // most definitely not the rusty way to do this.
-                        // it's ugly, but its efficient.
-                        if let Some((gpumesh, verts, idxs)) =
-                            MeshDescriptor::from_mesh(mesh, prim.clip_rect)
-                        {
-                            self.vertices.extend_from_slice(&verts);
-                            self.indices.extend_from_slice(&idxs);
-
-                            Some(gpumesh)
-                        } else {
-                            None
-                        }
-                    } else {
-                        panic!("paint callbacks not supported")
-                    }
-                })
-                .collect();
-
-            self.last_vtx_capacity = self.vertices.len();
-            self.last_idx_capacity = self.indices.len();
-
-            self.buffers.update_vertex_buffer(dev, &self.vertices)?;
-            self.buffers.update_index_buffer(dev, &self.indices)?;
+        // only there while a text field has focus.
+        let ime_rect = output
+            .platform_output
+            .ime
+            .map(|ime| ime.cursor_rect * output.pixels_per_point);
+
+        let mut shared = self.sink.lock();
+        shared.cursor_icon = output.platform_output.cursor_icon;
+        if ime_rect.is_some() && self.ime_rect != ime_rect {
+            shared.ime_position = ime_rect;
         }
+        drop(shared);
+        self.ime_rect = ime_rect;
+
+        Ok(())
+    }
 
-        // back up our state so we don't mess with the game and the game doesn't mess with us.
-        // i actually had the idea to use BeginStateBlock and co. to "cache" the state we set every frame,
-        // and just re-applying it everytime. just setting this manually takes around 50 microseconds on my machine.
-        let _state = DxState::setup(dev, self.get_viewport());
+    /// hand egui an event of your own, see [`InputSink::push_event`].
+    pub fn push_event(&mut self, event: Event) {
+        self.sink.push_event(event);
+    }
 
-        unsafe {
-            dev.SetStreamSource(
-                0,
-                self.buffers
-                    .vtx
-                    .as_ref()
-                    .expect("unable to get vertex buffer"),
-                0,
-                std::mem::size_of::<GpuVertex>() as _,
-            )?;
-
-            dev.SetIndices(
-                self.buffers
-                    .idx
-                    .as_ref()
-                    .expect("unable to get index buffer"),
-            )?;
-        }
+    /// feed a window message to egui, see [`InputSink::wnd_proc`].
+    #[inline]
+    pub fn wnd_proc(&self, umsg: u32, wparam: WPARAM, lparam: LPARAM) -> WndProcResult {
+        self.sink.wnd_proc(umsg, wparam, lparam)
+    }
 
-        let mut our_vtx_idx: usize = 0;
-        let mut our_idx_idx: usize = 0;
-
-        self.prims
-            .iter()
-            .try_for_each(|mesh: &MeshDescriptor| unsafe {
-                dev.SetScissorRect(&mesh.clip)?;
-
-                let texture = match mesh.texture_id {
-                    TextureId::Managed(id) => self.tex_man.get_by_id(TextureId::Managed(id)),
-                    TextureId::User(id) => self
-                        .handler
-                        .resolve_user_texture(id)
-                        .expect("unable to resolve user texture"),
-                };
-
-                dev.SetTexture(0, texture)?;
-
-                dev.DrawIndexedPrimitive(
-                    D3DPT_TRIANGLELIST,
-                    our_vtx_idx as _,
-                    0,
-                    mesh.vertices as _,
-                    our_idx_idx as _,
-                    (mesh.indices / 3usize) as _,
-                )?;
-
-                our_vtx_idx += mesh.vertices;
-                our_idx_idx += mesh.indices;
-                windows::core::Result::Ok(())
-            })?;
+    /// tell us whether your overlay is currently shown.
+    ///
+    /// this doesn't hide anything, `ui` still decides what to draw.
+    /// it only matters for [`InputBlocking::WhileOpen`].
+    pub fn set_open(&mut self, open: bool) {
+        self.sink.lock().open = open;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.sink.lock().open
+    }
 
-        if !output.textures_delta.is_empty() {
-            self.tex_man.process_free_deltas(&output.textures_delta);
+    pub fn set_input_blocking(&mut self, input_blocking: InputBlocking) {
+        self.sink.lock().input_blocking = input_blocking;
+    }
+
+    /// take the mouse from `WM_INPUT`, for games that use raw input and keep the cursor clipped or centered.
+    ///
+    /// we move a cursor of our own with the raw deltas, keep it inside the window,
+    /// and draw it on top of the ui while the overlay is open. the regular mouse messages are ignored meanwhile.
+    /// if the game hasn't registered the mouse for raw input, we do it for the window,
+    /// and remove that registration again once raw input is turned off or we're dropped.
+    ///
+    /// that has to happen on the window's thread, so turning it on takes effect with the next message
+    /// [`InputSink::wnd_proc`] sees. if we can't register, it stays off and [`Self::is_raw_input`] tells you so.
+    ///
+    /// if you hold back a consumed `WM_INPUT` from the game, still hand it to `DefWindowProc`.
+    pub fn set_raw_input(&mut self, raw_input: bool) {
+        let mut shared = self.sink.lock();
+        shared.raw_input_request = Some(raw_input);
+
+        if !raw_input {
+            shared.input_man.set_raw_input(false);
         }
+    }
 
-        Ok(())
+    pub fn is_raw_input(&self) -> bool {
+        self.sink.lock().input_man.is_raw_input()
     }
 
-    #[inline]
-    pub fn wnd_proc(&mut self, umsg: u32, wparam: WPARAM, lparam: LPARAM) {
-        // safe. we only write here, and only read elsewhere.
-        self.input_man.process(umsg, wparam.0, lparam.0);
+    /// take files dragged onto the window, egui gets them as [`egui::RawInput::dropped_files`].
+    ///
+    /// windows only tells us about a drop once it happened, so there are never any `hovered_files`.
+    /// we consume every `WM_DROPFILES` while this is on, the game won't see them.
+    pub fn set_drag_and_drop(&mut self, drag_and_drop: bool) {
+        // not while holding the lock, this can send the window messages.
+        platform::accept_files(self.hwnd, drag_and_drop);
+        self.sink.lock().input_man.set_accept_files(drag_and_drop);
+    }
+
+    pub fn is_drag_and_drop(&self) -> bool {
+        self.sink.lock().input_man.accepts_files()
+    }
+
+    /// where copying and pasting goes, the windows clipboard unless you say otherwise.
+    pub fn set_clipboard(&mut self, clipboard: impl ClipboardProvider + 'static) {
+        self.clipboard = Box::new(clipboard);
+    }
+
+    /// copy an image, e.g. a screenshot or a texture the user picked.
+    ///
+    /// egui itself only copies text for now, this goes through the same clipboard.
+    pub fn copy_image(&mut self, image: &ColorImage) {
+        self.clipboard.set_image(image);
+    }
+
+    /// switch where egui gets drawn, see [`RenderTarget`].
+    pub fn set_render_target(&mut self, render_target: RenderTarget) {
+        self.renderer.cache_mut().set_render_target(render_target);
+    }
+}
+
+impl<T> Drop for EguiDx9<T> {
+    fn drop(&mut self) {
+        // the window procedure might not see another message, so no waiting for it.
+        // removing a registration doesn't care which thread it comes from.
+        let owns_raw_mouse = std::mem::take(&mut self.sink.lock().owns_raw_mouse);
+
+        if owns_raw_mouse {
+            let _ = platform::unregister_raw_mouse();
+        }
     }
 }
 
 impl<T> EguiDx9<T> {
     #[allow(clippy::cast_sign_loss)]
-    fn get_screen_size(&self) -> (u32, u32) {
+    fn get_screen_size(&self) -> Result<[u32; 2]> {
         let mut rect = RECT::default();
         unsafe {
-            GetClientRect(self.hwnd, &mut rect).expect("Failed to GetClientRect()");
+            GetClientRect(self.hwnd, &mut rect)?;
         }
-        (
+        Ok([
             (rect.right - rect.left) as u32,
             (rect.bottom - rect.top) as u32,
-        )
-    }
-
-    fn get_viewport(&self) -> D3DVIEWPORT9 {
-        let (w, h) = self.get_screen_size();
-        D3DVIEWPORT9 {
-            X: 0,
-            Y: 0,
-            Width: w as _,
-            Height: h as _,
-            MinZ: 0.,
-            MaxZ: 1.,
-        }
-    }
-}
-
-impl<H> Drop for EguiDx9<H> {
-    fn drop(&mut self) {
-        self.buffers.delete_buffers();
-        self.tex_man.deallocate_textures();
+        ])
     }
 }
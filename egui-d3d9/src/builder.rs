@@ -0,0 +1,163 @@
+use egui::{Context, FontDefinitions, Style, TextureOptions};
+use windows::Win32::{Foundation::HWND, Graphics::Direct3D9::IDirect3DDevice9};
+
+use crate::{
+    ClipboardProvider, EguiDx9, InputBlocking, RenderTarget, Result, UIHandler, WheelConfig,
+};
+
+/// configures and creates an [`EguiDx9`].
+///
+/// everything is optional, the defaults match what [`EguiDx9::init`] does.
+///
+/// ```ignore
+/// let app = EguiDx9Builder::new()
+///     .context(shared_ctx.clone())
+///     .buffer_capacity(65536, 65536)
+///     .reactive(true)
+///     .build(&dev, hwnd, handler)?;
+/// ```
+#[must_use]
+pub struct EguiDx9Builder {
+    pub(crate) ctx: Option<Context>,
+    pub(crate) vertex_capacity: usize,
+    pub(crate) index_capacity: usize,
+    pub(crate) pixels_per_point: Option<f32>,
+    pub(crate) ui_scale: f32,
+    pub(crate) style: Option<Style>,
+    pub(crate) fonts: Option<FontDefinitions>,
+    pub(crate) reactive: bool,
+    pub(crate) input_blocking: InputBlocking,
+    pub(crate) sampler: TextureOptions,
+    pub(crate) render_target: RenderTarget,
+    pub(crate) raw_input: bool,
+    pub(crate) wheel: WheelConfig,
+    pub(crate) clipboard: Option<Box<dyn ClipboardProvider>>,
+    pub(crate) drag_and_drop: bool,
+}
+
+impl Default for EguiDx9Builder {
+    fn default() -> Self {
+        Self {
+            ctx: None,
+            vertex_capacity: 16384,
+            index_capacity: 16384,
+            pixels_per_point: None,
+            ui_scale: 1.,
+            style: None,
+            fonts: None,
+            reactive: false,
+            input_blocking: InputBlocking::default(),
+            sampler: TextureOptions::LINEAR,
+            render_target: RenderTarget::default(),
+            raw_input: false,
+            wheel: WheelConfig::default(),
+            clipboard: None,
+            drag_and_drop: false,
+        }
+    }
+}
+
+impl EguiDx9Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// use an existing context instead of creating a fresh one.
+    ///
+    /// we install our own repaint callback on it, to know when reactive mode has to run again.
+    pub fn context(mut self, ctx: Context) -> Self {
+        self.ctx = Some(ctx);
+        self
+    }
+
+    /// initial capacity of the vertex and index buffers.
+    /// they still grow if a frame needs more, this just avoids the first few re-allocations.
+    pub fn buffer_capacity(mut self, vertices: usize, indices: usize) -> Self {
+        self.vertex_capacity = vertices;
+        self.index_capacity = indices;
+        self
+    }
+
+    /// use this many pixels per point instead of asking the window for its dpi.
+    pub fn pixels_per_point(mut self, pixels_per_point: f32) -> Self {
+        self.pixels_per_point = Some(pixels_per_point);
+        self
+    }
+
+    /// scale the whole ui by this factor, on top of the dpi (or [`Self::pixels_per_point`]).
+    pub fn ui_scale(mut self, scale: f32) -> Self {
+        self.ui_scale = scale;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    pub fn fonts(mut self, fonts: FontDefinitions) -> Self {
+        self.fonts = Some(fonts);
+        self
+    }
+
+    /// only run the ui once something changes, see [`EguiDx9::init`].
+    pub fn reactive(mut self, reactive: bool) -> Self {
+        self.reactive = reactive;
+        self
+    }
+
+    /// which input [`EguiDx9::wnd_proc`] tells you to keep from the game.
+    pub fn input_blocking(mut self, input_blocking: InputBlocking) -> Self {
+        self.input_blocking = input_blocking;
+        self
+    }
+
+    /// filtering and wrapping for native and user textures.
+    ///
+    /// egui's own textures, e.g. images loaded through `egui_extras`, are sampled the way egui asks for.
+    pub fn sampler(mut self, sampler: TextureOptions) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// where egui gets drawn, see [`RenderTarget`].
+    pub fn render_target(mut self, render_target: RenderTarget) -> Self {
+        self.render_target = render_target;
+        self
+    }
+
+    /// move the mouse with `WM_INPUT` instead of the regular mouse messages, see [`EguiDx9::set_raw_input`].
+    pub fn raw_input(mut self, raw_input: bool) -> Self {
+        self.raw_input = raw_input;
+        self
+    }
+
+    /// how the mouse wheel scrolls and zooms.
+    pub fn wheel(mut self, wheel: WheelConfig) -> Self {
+        self.wheel = wheel;
+        self
+    }
+
+    /// where copying and pasting goes, e.g. a [`MemoryClipboard`](crate::MemoryClipboard) to keep it away from the system.
+    pub fn clipboard(mut self, clipboard: impl ClipboardProvider + 'static) -> Self {
+        self.clipboard = Some(Box::new(clipboard));
+        self
+    }
+
+    /// take files dragged onto the window, see [`EguiDx9::set_drag_and_drop`].
+    pub fn drag_and_drop(mut self, drag_and_drop: bool) -> Self {
+        self.drag_and_drop = drag_and_drop;
+        self
+    }
+
+    /// # Errors
+    /// If buffers cannot be created.
+    pub fn build<H: UIHandler>(
+        self,
+        dev: &IDirect3DDevice9,
+        hwnd: HWND,
+        handler: H,
+    ) -> Result<EguiDx9<H>> {
+        EguiDx9::from_builder(self, dev, hwnd, handler)
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use egui::ColorImage;
+#[cfg(windows)]
+use windows::Win32::{
+    Foundation::{GlobalFree, HANDLE, HGLOBAL},
+    System::{
+        DataExchange::{
+            CloseClipboard, EmptyClipboard, GetClipboardData, IsClipboardFormatAvailable,
+            OpenClipboard, SetClipboardData,
+        },
+        Memory::{GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE},
+    },
+};
+
+/// where copying and pasting goes.
+///
+/// [`NativeClipboard`] is the one everyone shares, [`MemoryClipboard`] keeps it to ourselves.
+pub trait ClipboardProvider {
+    /// the text to paste, `None` if there's no text to paste.
+    fn get_text(&mut self) -> Option<String>;
+
+    fn set_text(&mut self, text: &str);
+
+    /// copy an image, premultiplied like all of egui's.
+    ///
+    /// egui doesn't ask for this on its own yet, see [`EguiDx9::copy_image`](crate::EguiDx9::copy_image).
+    fn set_image(&mut self, image: &ColorImage);
+}
+
+/// a clipboard that only lives in memory, for tests or when the real one is off limits.
+///
+/// clones share their contents, keep one around to look at what egui copied.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryClipboard {
+    contents: Arc<Mutex<Contents>>,
+}
+
+#[derive(Debug, Default)]
+struct Contents {
+    text: Option<String>,
+    image: Option<ColorImage>,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(&self) -> Option<String> {
+        self.lock().text.clone()
+    }
+
+    pub fn image(&self) -> Option<ColorImage> {
+        self.lock().image.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Contents> {
+        // a clone panicking elsewhere shouldn't take copy and paste down with it, the contents are always whole.
+        self.contents.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl ClipboardProvider for MemoryClipboard {
+    fn get_text(&mut self) -> Option<String> {
+        self.text()
+    }
+
+    fn set_text(&mut self, text: &str) {
+        let mut contents = self.lock();
+        contents.text = Some(text.to_owned());
+        contents.image = None;
+    }
+
+    fn set_image(&mut self, image: &ColorImage) {
+        let mut contents = self.lock();
+        contents.text = None;
+        contents.image = Some(image.clone());
+    }
+}
+
+/// the windows clipboard.
+///
+/// failing to open it, e.g. because another program has it open, just means nothing gets copied or pasted.
+#[cfg(windows)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NativeClipboard;
+
+/// `CF_DIB`
+#[cfg(windows)]
+const CF_DIB: u32 = 8;
+/// `CF_UNICODETEXT`
+#[cfg(windows)]
+const CF_UNICODETEXT: u32 = 13;
+
+#[cfg(windows)]
+impl ClipboardProvider for NativeClipboard {
+    fn get_text(&mut self) -> Option<String> {
+        unsafe {
+            IsClipboardFormatAvailable(CF_UNICODETEXT).ok()?;
+            OpenClipboard(None).ok()?;
+
+            let text = read_text();
+
+            let _ = CloseClipboard();
+            text
+        }
+    }
+
+    fn set_text(&mut self, text: &str) {
+        let data = text
+            .encode_utf16()
+            .chain([0])
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+
+        unsafe { write(CF_UNICODETEXT, &data) };
+    }
+
+    fn set_image(&mut self, image: &ColorImage) {
+        unsafe { write(CF_DIB, &image_to_dib(image)) };
+    }
+}
+
+#[cfg(windows)]
+unsafe fn read_text() -> Option<String> {
+    let mem = HGLOBAL(GetClipboardData(CF_UNICODETEXT).ok()?.0);
+
+    let ptr = GlobalLock(mem).cast::<u16>();
+    if ptr.is_null() {
+        return None;
+    }
+
+    // the block can be bigger than the text, which ends at the first nul.
+    let units = std::slice::from_raw_parts(ptr, GlobalSize(mem) / 2);
+    let len = units
+        .iter()
+        .position(|&unit| unit == 0)
+        .unwrap_or(units.len());
+    let text = String::from_utf16_lossy(&units[..len]);
+
+    let _ = GlobalUnlock(mem);
+    Some(text)
+}
+
+/// replace whatever is on the clipboard with `data`.
+#[cfg(windows)]
+unsafe fn write(format: u32, data: &[u8]) {
+    let Ok(mem) = GlobalAlloc(GMEM_MOVEABLE, data.len()) else {
+        return;
+    };
+
+    let ptr = GlobalLock(mem);
+    if ptr.is_null() {
+        let _ = GlobalFree(Some(mem));
+        return;
+    }
+
+    std::ptr::copy_nonoverlapping(data.as_ptr(), ptr.cast(), data.len());
+    let _ = GlobalUnlock(mem);
+
+    if OpenClipboard(None).is_err() {
+        let _ = GlobalFree(Some(mem));
+        return;
+    }
+
+    // the clipboard owns the memory once this succeeds.
+    let _ = EmptyClipboard();
+    if SetClipboardData(format, Some(HANDLE(mem.0))).is_err() {
+        let _ = GlobalFree(Some(mem));
+    }
+
+    let _ = CloseClipboard();
+}
+
+/// a packed dib, what `CF_DIB` wants: a `BITMAPINFOHEADER` followed by 32 bit bgra rows, bottom up.
+#[cfg(windows)]
+fn image_to_dib(image: &ColorImage) -> Vec<u8> {
+    const HEADER_SIZE: u32 = 40;
+
+    let [width, height] = image.size;
+    let image_size = (width * height * 4) as u32;
+
+    let mut dib = Vec::with_capacity(HEADER_SIZE as usize + image_size as usize);
+    dib.extend(HEADER_SIZE.to_le_bytes());
+    dib.extend((width as i32).to_le_bytes());
+    // positive means bottom up.
+    dib.extend((height as i32).to_le_bytes());
+    // planes, bits per pixel
+    dib.extend(1u16.to_le_bytes());
+    dib.extend(32u16.to_le_bytes());
+    // BI_RGB
+    dib.extend(0u32.to_le_bytes());
+    dib.extend(image_size.to_le_bytes());
+    // resolution and palette, none of which we have
+    dib.extend([0; 16]);
+
+    for row in image.pixels.chunks_exact(width.max(1)).rev() {
+        for pixel in row {
+            let [r, g, b, a] = pixel.to_srgba_unmultiplied();
+            dib.extend([b, g, r, a]);
+        }
+    }
+
+    dib
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn survives_a_poisoned_lock() {
+        let mut clipboard = MemoryClipboard::new();
+        clipboard.set_text("before");
+
+        let clone = clipboard.clone();
+        let _ = std::thread::spawn(move || {
+            let _contents = clone.lock();
+            panic!("while holding the clipboard");
+        })
+        .join();
+        assert!(clipboard.contents.is_poisoned());
+
+        assert_eq!(clipboard.get_text().as_deref(), Some("before"));
+        clipboard.set_text("after");
+        assert_eq!(clipboard.text().as_deref(), Some("after"));
+    }
+}
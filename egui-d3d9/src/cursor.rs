@@ -0,0 +1,138 @@
+use egui::CursorIcon;
+
+/// the system cursors we pick from, one for each `IDC_*` we use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SystemCursor {
+    Arrow,
+    Help,
+    Hand,
+    AppStarting,
+    Wait,
+    Cross,
+    IBeam,
+    No,
+    SizeAll,
+    SizeWe,
+    SizeNs,
+    SizeNesw,
+    SizeNwse,
+}
+
+/// the system cursor closest to what egui wants, `None` hides the cursor.
+///
+/// windows has nothing for some of egui's icons, those fall back to something that's at least close.
+pub const fn system_cursor(icon: CursorIcon) -> Option<SystemCursor> {
+    Some(match icon {
+        CursorIcon::None => return None,
+        CursorIcon::Default
+        | CursorIcon::ContextMenu
+        | CursorIcon::Alias
+        | CursorIcon::Copy
+        | CursorIcon::ZoomIn
+        | CursorIcon::ZoomOut => SystemCursor::Arrow,
+        CursorIcon::Help => SystemCursor::Help,
+        CursorIcon::PointingHand | CursorIcon::Grab => SystemCursor::Hand,
+        CursorIcon::Progress => SystemCursor::AppStarting,
+        CursorIcon::Wait => SystemCursor::Wait,
+        CursorIcon::Cell | CursorIcon::Crosshair => SystemCursor::Cross,
+        CursorIcon::Text | CursorIcon::VerticalText => SystemCursor::IBeam,
+        CursorIcon::NoDrop | CursorIcon::NotAllowed => SystemCursor::No,
+        CursorIcon::Move | CursorIcon::Grabbing | CursorIcon::AllScroll => SystemCursor::SizeAll,
+        CursorIcon::ResizeHorizontal
+        | CursorIcon::ResizeEast
+        | CursorIcon::ResizeWest
+        | CursorIcon::ResizeColumn => SystemCursor::SizeWe,
+        CursorIcon::ResizeVertical
+        | CursorIcon::ResizeNorth
+        | CursorIcon::ResizeSouth
+        | CursorIcon::ResizeRow => SystemCursor::SizeNs,
+        CursorIcon::ResizeNeSw | CursorIcon::ResizeNorthEast | CursorIcon::ResizeSouthWest => {
+            SystemCursor::SizeNesw
+        }
+        CursorIcon::ResizeNwSe | CursorIcon::ResizeNorthWest | CursorIcon::ResizeSouthEast => {
+            SystemCursor::SizeNwse
+        }
+    })
+}
+
+/// only let through what a browser or mail client should handle.
+/// `ShellExecute` happily runs executables and opens local files otherwise.
+pub fn is_web_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+
+    ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_icon_but_none_is_shown() {
+        for icon in CursorIcon::ALL {
+            assert_eq!(
+                system_cursor(icon).is_none(),
+                icon == CursorIcon::None,
+                "{icon:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn resize_directions_match() {
+        let cases = [
+            (CursorIcon::ResizeEast, SystemCursor::SizeWe),
+            (CursorIcon::ResizeColumn, SystemCursor::SizeWe),
+            (CursorIcon::ResizeNorth, SystemCursor::SizeNs),
+            (CursorIcon::ResizeRow, SystemCursor::SizeNs),
+            (CursorIcon::ResizeNorthEast, SystemCursor::SizeNesw),
+            (CursorIcon::ResizeSouthWest, SystemCursor::SizeNesw),
+            (CursorIcon::ResizeNorthWest, SystemCursor::SizeNwse),
+            (CursorIcon::ResizeSouthEast, SystemCursor::SizeNwse),
+        ];
+
+        for (icon, cursor) in cases {
+            assert_eq!(system_cursor(icon), Some(cursor), "{icon:?}");
+        }
+    }
+
+    #[test]
+    fn text_and_links() {
+        assert_eq!(system_cursor(CursorIcon::Text), Some(SystemCursor::IBeam));
+        assert_eq!(
+            system_cursor(CursorIcon::PointingHand),
+            Some(SystemCursor::Hand)
+        );
+        assert_eq!(
+            system_cursor(CursorIcon::Default),
+            Some(SystemCursor::Arrow)
+        );
+    }
+
+    #[test]
+    fn only_web_urls_are_opened() {
+        let cases = [
+            ("https://github.com/emilk/egui", true),
+            ("http://example.com", true),
+            ("mailto:someone@example.com", true),
+            ("HTTPS://Example.com", true),
+            ("HtTp://example.com", true),
+            ("  https://example.com", true),
+            ("\thttp://example.com", true),
+            ("file:///C:/Windows/System32/calc.exe", false),
+            ("javascript:alert(1)", false),
+            ("C:\\Windows\\System32\\calc.exe", false),
+            ("calc.exe", false),
+            ("/usr/bin/sh", false),
+            ("..\\x.exe", false),
+            ("https:", false),
+            ("", false),
+        ];
+
+        for (url, web) in cases {
+            assert_eq!(is_web_url(url), web, "{url:?}");
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use egui::{epaint::PaintCallbackInfo, PaintCallback, TextureOptions};
+use windows::Win32::{
+    Foundation::{E_FAIL, HANDLE, POINT, RECT},
+    Graphics::Direct3D9::{
+        IDirect3DDevice9, IDirect3DIndexBuffer9, IDirect3DTexture9, IDirect3DVertexBuffer9,
+        D3DFMT_A8R8G8B8, D3DFMT_INDEX32, D3DFVF_DIFFUSE, D3DFVF_TEX1, D3DFVF_XYZ, D3DLOCKED_RECT,
+        D3DLOCK_DISCARD, D3DLOCK_READONLY, D3DPOOL_DEFAULT, D3DPOOL_SYSTEMMEM, D3DPT_TRIANGLELIST,
+        D3DUSAGE_DYNAMIC, D3DUSAGE_WRITEONLY, D3DVIEWPORT9,
+    },
+};
+
+use crate::{
+    state::{self, DxState, StateCache},
+    CallbackFn, ClipRect, Error, GpuVertex, RenderDevice, Result, TextureColor,
+};
+
+pub const FVF_CUSTOMVERTEX: u32 = D3DFVF_XYZ | D3DFVF_DIFFUSE | D3DFVF_TEX1;
+
+impl RenderDevice for IDirect3DDevice9 {
+    type VertexBuffer = IDirect3DVertexBuffer9;
+    type IndexBuffer = IDirect3DIndexBuffer9;
+    type Texture = IDirect3DTexture9;
+    type Cache = StateCache;
+    type Frame = DxState;
+
+    fn is_lost(&self) -> bool {
+        unsafe { self.TestCooperativeLevel() }.is_err()
+    }
+
+    fn create_vertex_buffer(&self, vertices: usize) -> Result<IDirect3DVertexBuffer9> {
+        unsafe {
+            let mut vertex_buffer: Option<IDirect3DVertexBuffer9> = None;
+
+            self.CreateVertexBuffer(
+                (vertices * std::mem::size_of::<GpuVertex>()) as u32,
+                (D3DUSAGE_DYNAMIC | D3DUSAGE_WRITEONLY) as _,
+                FVF_CUSTOMVERTEX,
+                D3DPOOL_DEFAULT,
+                &mut vertex_buffer,
+                std::ptr::null_mut::<HANDLE>(),
+            )
+            .map_err(Error::BufferCreation)?;
+
+            vertex_buffer.ok_or_else(|| Error::BufferCreation(E_FAIL.into()))
+        }
+    }
+
+    fn create_index_buffer(&self, indices: usize) -> Result<IDirect3DIndexBuffer9> {
+        let mut index_buffer: Option<IDirect3DIndexBuffer9> = None;
+        unsafe {
+            self.CreateIndexBuffer(
+                (indices * std::mem::size_of::<u32>()) as u32,
+                (D3DUSAGE_DYNAMIC | D3DUSAGE_WRITEONLY) as _,
+                D3DFMT_INDEX32,
+                D3DPOOL_DEFAULT,
+                &mut index_buffer,
+                std::ptr::null_mut::<HANDLE>(),
+            )
+            .map_err(Error::BufferCreation)?;
+        }
+
+        index_buffer.ok_or_else(|| Error::BufferCreation(E_FAIL.into()))
+    }
+
+    fn write_vertices(
+        &self,
+        buffer: &IDirect3DVertexBuffer9,
+        vertices: &[GpuVertex],
+    ) -> Result<()> {
+        unsafe {
+            let mut locked: *mut GpuVertex = std::mem::zeroed();
+
+            buffer
+                .Lock(
+                    0,
+                    std::mem::size_of_val(vertices) as u32,
+                    (&raw mut locked).cast(),
+                    D3DLOCK_DISCARD as _,
+                )
+                .map_err(Error::BufferCreation)?;
+
+            std::slice::from_raw_parts_mut(locked, vertices.len()).copy_from_slice(vertices);
+
+            buffer.Unlock()?;
+        }
+
+        Ok(())
+    }
+
+    fn write_indices(&self, buffer: &IDirect3DIndexBuffer9, indices: &[u32]) -> Result<()> {
+        unsafe {
+            let mut locked: *mut u32 = std::mem::zeroed();
+
+            buffer
+                .Lock(
+                    0,
+                    std::mem::size_of_val(indices) as u32,
+                    (&raw mut locked).cast(),
+                    D3DLOCK_DISCARD as _,
+                )
+                .map_err(Error::BufferCreation)?;
+
+            std::slice::from_raw_parts_mut(locked, indices.len()).copy_from_slice(indices);
+
+            buffer.Unlock()?;
+        }
+
+        Ok(())
+    }
+
+    fn create_texture(
+        &self,
+        pixels: &[TextureColor],
+        size: [usize; 2],
+    ) -> Result<IDirect3DTexture9> {
+        let temp_tex = create_temporary_texture(self, pixels, size)?;
+        let mut texture: Option<IDirect3DTexture9> = None;
+
+        unsafe {
+            self.CreateTexture(
+                size[0] as _,
+                size[1] as _,
+                1,
+                D3DUSAGE_DYNAMIC as _,
+                D3DFMT_A8R8G8B8,
+                D3DPOOL_DEFAULT,
+                &mut texture,
+                std::ptr::null_mut(),
+            )?;
+
+            let texture = texture.ok_or(Error::Win32(E_FAIL.into()))?;
+
+            self.UpdateTexture(&temp_tex, &texture)?;
+
+            Ok(texture)
+        }
+    }
+
+    fn update_texture(
+        &self,
+        texture: &IDirect3DTexture9,
+        pixels: &[TextureColor],
+        pos: [usize; 2],
+        size: [usize; 2],
+    ) -> Result<()> {
+        let temp_tex = create_temporary_texture(self, pixels, size)?;
+
+        unsafe {
+            let src_surface = temp_tex.GetSurfaceLevel(0)?;
+
+            let dst_surface = texture.GetSurfaceLevel(0)?;
+
+            self.UpdateSurface(
+                &src_surface,
+                &RECT {
+                    left: 0,
+                    top: 0,
+                    right: size[0] as _,
+                    bottom: size[1] as _,
+                },
+                &dst_surface,
+                &POINT {
+                    x: pos[0] as _,
+                    y: pos[1] as _,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn invalidate_cache(cache: &mut StateCache) {
+        cache.invalidate();
+    }
+
+    fn begin_frame(&self, cache: &mut StateCache, screen_size: [u32; 2]) -> Result<DxState> {
+        let viewport = D3DVIEWPORT9 {
+            X: 0,
+            Y: 0,
+            Width: screen_size[0],
+            Height: screen_size[1],
+            MinZ: 0.,
+            MaxZ: 1.,
+        };
+
+        Ok(DxState::setup(self, cache, viewport)?)
+    }
+
+    fn reapply(&self, frame: &mut DxState) -> Result<()> {
+        Ok(frame.reapply()?)
+    }
+
+    fn end_frame(&self, mut frame: DxState) -> Result<()> {
+        Ok(frame.release()?)
+    }
+
+    fn bind_buffers(
+        &self,
+        vertices: &IDirect3DVertexBuffer9,
+        indices: &IDirect3DIndexBuffer9,
+    ) -> Result<()> {
+        unsafe {
+            self.SetStreamSource(0, vertices, 0, std::mem::size_of::<GpuVertex>() as _)?;
+            self.SetIndices(indices)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_clip(&self, clip: ClipRect) -> Result<()> {
+        unsafe { self.SetScissorRect(&rect(clip))? };
+
+        Ok(())
+    }
+
+    fn set_texture(&self, texture: &IDirect3DTexture9, sampler: TextureOptions) -> Result<()> {
+        unsafe { self.SetTexture(0, texture)? };
+        state::set_sampler(self, sampler)?;
+
+        Ok(())
+    }
+
+    fn draw_indexed(
+        &self,
+        base_vertex: usize,
+        vertices: usize,
+        first_index: usize,
+        triangles: usize,
+    ) -> Result<()> {
+        unsafe {
+            self.DrawIndexedPrimitive(
+                D3DPT_TRIANGLELIST,
+                base_vertex as _,
+                0,
+                vertices as _,
+                first_index as _,
+                triangles as _,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn paint_callback(&self, callback: &PaintCallback, info: PaintCallbackInfo) -> Result<bool> {
+        let Some(callback) = callback.callback.downcast_ref::<CallbackFn>() else {
+            return Ok(false);
+        };
+
+        let area = info.viewport_in_pixels();
+        let clip = info.clip_rect_in_pixels();
+
+        unsafe {
+            self.SetViewport(&D3DVIEWPORT9 {
+                X: area.left_px as _,
+                Y: area.top_px as _,
+                Width: area.width_px as _,
+                Height: area.height_px as _,
+                MinZ: 0.,
+                MaxZ: 1.,
+            })?;
+
+            self.SetScissorRect(&RECT {
+                left: clip.left_px,
+                top: clip.top_px,
+                right: clip.left_px + clip.width_px,
+                bottom: clip.top_px + clip.height_px,
+            })?;
+        }
+
+        (callback.f)(info, self);
+
+        Ok(true)
+    }
+}
+
+fn rect(clip: ClipRect) -> RECT {
+    RECT {
+        left: clip.left as _,
+        top: clip.top as _,
+        right: clip.right as _,
+        bottom: clip.bottom as _,
+    }
+}
+
+fn create_temporary_texture(
+    dev: &IDirect3DDevice9,
+    buf: &[TextureColor],
+    size: [usize; 2],
+) -> Result<IDirect3DTexture9> {
+    unsafe {
+        let mut temp_texture: Option<IDirect3DTexture9> = None;
+
+        dev.CreateTexture(
+            size[0] as _,
+            size[1] as _,
+            1,
+            D3DUSAGE_DYNAMIC as _,
+            D3DFMT_A8R8G8B8,
+            D3DPOOL_SYSTEMMEM,
+            &mut temp_texture,
+            std::ptr::null_mut(),
+        )?;
+
+        let temp_texture = temp_texture.ok_or(Error::Win32(E_FAIL.into()))?;
+
+        let mut locked_rect = D3DLOCKED_RECT::default();
+
+        temp_texture.LockRect(
+            0,
+            &mut locked_rect,
+            std::ptr::null_mut(),
+            D3DLOCK_DISCARD as u32 | D3DLOCK_READONLY as u32,
+        )?;
+
+        std::slice::from_raw_parts_mut(locked_rect.pBits.cast::<TextureColor>(), size[0] * size[1])
+            .copy_from_slice(buf);
+
+        temp_texture.UnlockRect(0)?;
+
+        Ok(temp_texture)
+    }
+}
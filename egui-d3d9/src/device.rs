@@ -0,0 +1,89 @@
+use egui::{epaint::PaintCallbackInfo, PaintCallback, TextureOptions};
+
+use crate::{GpuVertex, Result, TextureColor};
+
+/// a scissor rect in pixels, already clamped to the screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClipRect {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+/// everything the renderer needs from a graphics api.
+///
+/// `IDirect3DDevice9` implements this on windows,
+/// [`RecordingDevice`](crate::RecordingDevice) just writes down what it was asked to do,
+/// so the whole pipeline can run without a gpu.
+///
+/// all methods take `&self`, just like the com interfaces do.
+pub trait RenderDevice {
+    type VertexBuffer;
+    type IndexBuffer;
+    type Texture: Clone;
+    /// whatever the device wants to keep around between frames, e.g. state blocks.
+    type Cache;
+    /// whatever the device needs to restore the host's state once we're done drawing.
+    type Frame;
+
+    /// true while nothing can be drawn, e.g. a lost d3d9 device.
+    fn is_lost(&self) -> bool;
+
+    fn create_vertex_buffer(&self, vertices: usize) -> Result<Self::VertexBuffer>;
+
+    fn create_index_buffer(&self, indices: usize) -> Result<Self::IndexBuffer>;
+
+    /// overwrite the start of the buffer, it is always big enough.
+    fn write_vertices(&self, buffer: &Self::VertexBuffer, vertices: &[GpuVertex]) -> Result<()>;
+
+    /// overwrite the start of the buffer, it is always big enough.
+    fn write_indices(&self, buffer: &Self::IndexBuffer, indices: &[u32]) -> Result<()>;
+
+    fn create_texture(&self, pixels: &[TextureColor], size: [usize; 2]) -> Result<Self::Texture>;
+
+    /// overwrite the area starting at `pos` with `pixels`, which are `size` big.
+    fn update_texture(
+        &self,
+        texture: &Self::Texture,
+        pixels: &[TextureColor],
+        pos: [usize; 2],
+        size: [usize; 2],
+    ) -> Result<()>;
+
+    /// drop everything in the cache that doesn't survive a reset.
+    fn invalidate_cache(cache: &mut Self::Cache);
+
+    /// back up the host's state and set up ours for drawing to a screen of `screen_size` pixels.
+    fn begin_frame(&self, cache: &mut Self::Cache, screen_size: [u32; 2]) -> Result<Self::Frame>;
+
+    /// set our state again, after a paint callback did whatever it wanted.
+    fn reapply(&self, frame: &mut Self::Frame) -> Result<()>;
+
+    /// give the host its state back.
+    fn end_frame(&self, frame: Self::Frame) -> Result<()>;
+
+    fn bind_buffers(
+        &self,
+        vertices: &Self::VertexBuffer,
+        indices: &Self::IndexBuffer,
+    ) -> Result<()>;
+
+    fn set_clip(&self, clip: ClipRect) -> Result<()>;
+
+    /// bind `texture` for the next draws, sampled with `sampler`.
+    fn set_texture(&self, texture: &Self::Texture, sampler: TextureOptions) -> Result<()>;
+
+    /// draw `triangles` triangles, starting at `first_index`.
+    /// indices are relative to `base_vertex`, which is followed by `vertices` vertices.
+    fn draw_indexed(
+        &self,
+        base_vertex: usize,
+        vertices: usize,
+        first_index: usize,
+        triangles: usize,
+    ) -> Result<()>;
+
+    /// run a paint callback. callbacks meant for other backends return `false` and are skipped.
+    fn paint_callback(&self, callback: &PaintCallback, info: PaintCallbackInfo) -> Result<bool>;
+}
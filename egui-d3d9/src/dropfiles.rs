@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use egui::DroppedFile;
+
+/// what egui gets for a file dropped on the window.
+pub fn dropped_file(path: PathBuf) -> DroppedFile {
+    DroppedFile {
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        mime: guess_mime(&path).to_owned(),
+        last_modified: std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok(),
+        path: Some(path),
+        bytes: None,
+    }
+}
+
+/// a mime type going by the extension, for what people are likely to drop on a game overlay.
+pub fn guess_mime(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "txt" | "log" | "cfg" | "ini" => "text/plain",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "toml" => "application/toml",
+        "yaml" | "yml" => "application/yaml",
+        "xml" => "application/xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "wav" => "audio/wav",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mime_goes_by_the_extension() {
+        let cases = [
+            ("notes.txt", "text/plain"),
+            ("config.json", "application/json"),
+            ("screenshot.png", "image/png"),
+            ("photo.jpeg", "image/jpeg"),
+            // windows doesn't care about case, neither do we.
+            ("SCREENSHOT.PNG", "image/png"),
+            ("Photo.JpG", "image/jpeg"),
+            ("dir/sub.dir/archive.zip", "application/zip"),
+            ("C:\\Users\\me\\font.TTF", "font/ttf"),
+            // only the last extension counts.
+            ("backup.png.zip", "application/zip"),
+            // no extension at all.
+            ("README", "application/octet-stream"),
+            (".gitignore", "application/octet-stream"),
+            ("trailing.", "application/octet-stream"),
+            // nothing we know.
+            ("game.exe", "application/octet-stream"),
+            ("save.dat", "application/octet-stream"),
+        ];
+
+        for (path, mime) in cases {
+            assert_eq!(guess_mime(Path::new(path)), mime, "{path:?}");
+        }
+    }
+
+    #[test]
+    fn dropped_files_keep_their_path_and_name() {
+        let file = dropped_file(PathBuf::from("somewhere/that/does/not/exist/level.json"));
+
+        assert_eq!(file.name, "level.json");
+        assert_eq!(file.mime, "application/json");
+        assert_eq!(
+            file.path.as_deref(),
+            Some(Path::new("somewhere/that/does/not/exist/level.json"))
+        );
+        // nothing to read the metadata of, and the bytes stay on disk.
+        assert_eq!(file.last_modified, None);
+        assert!(file.bytes.is_none());
+    }
+}
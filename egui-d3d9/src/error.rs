@@ -0,0 +1,59 @@
+use std::fmt;
+
+use egui::TextureId;
+
+/// errors coming from the underlying api.
+#[cfg(windows)]
+pub type PlatformError = windows::core::Error;
+/// errors coming from the underlying api, there is none outside of windows.
+#[cfg(not(windows))]
+pub type PlatformError = std::convert::Infallible;
+
+/// everything that can go wrong inside the backend.
+///
+/// none of these are fatal for the host, you can log them and try again next frame.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// the device is lost or waiting for a reset, nothing can be drawn right now.
+    DeviceLost,
+    /// egui referenced a texture we never received (or already freed).
+    MissingTexture(TextureId),
+    /// creating or writing to our vertex/index buffers failed.
+    BufferCreation(PlatformError),
+    /// the id was never handed out by [`EguiDx9::register_native_texture`](crate::EguiDx9::register_native_texture),
+    /// or has been freed already.
+    UnknownNativeTexture(TextureId),
+    /// any other Win32/Direct3D call failed.
+    Win32(PlatformError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DeviceLost => write!(f, "the device is lost"),
+            Self::MissingTexture(id) => write!(f, "missing texture {id:?}"),
+            Self::BufferCreation(err) => write!(f, "unable to create buffers: {err}"),
+            Self::UnknownNativeTexture(id) => write!(f, "unknown native texture {id:?}"),
+            Self::Win32(err) => write!(f, "win32 error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::BufferCreation(err) | Self::Win32(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl From<windows::core::Error> for Error {
+    fn from(value: windows::core::Error) -> Self {
+        Self::Win32(value)
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use egui::{Color32, ColorImage};
+
+/// set this to anything to (re-)write golden images instead of comparing against them.
+///
+/// e.g. `EGUI_D3D9_BLESS=1 cargo test --test golden` after a change that is meant to look different.
+/// look at the new images before committing them.
+pub const BLESS_ENV: &str = "EGUI_D3D9_BLESS";
+
+/// how far apart two images of the same size are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageDiff {
+    /// pixels where any channel differs by more than the tolerance.
+    pub differing_pixels: usize,
+    /// the biggest difference in any channel of any pixel.
+    pub max_difference: u8,
+}
+
+#[derive(Debug)]
+pub enum GoldenError {
+    Io(io::Error),
+    /// the golden image isn't a file we wrote.
+    Format(PathBuf),
+    /// there is no golden image yet, set [`BLESS_ENV`] to write it.
+    Missing(PathBuf),
+    SizeMismatch {
+        expected: [usize; 2],
+        actual: [usize; 2],
+    },
+    Mismatch(ImageDiff),
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Format(path) => write!(f, "{} is not a valid golden image", path.display()),
+            Self::Missing(path) => write!(
+                f,
+                "missing golden image {}, set {BLESS_ENV} to create it",
+                path.display()
+            ),
+            Self::SizeMismatch { expected, actual } => {
+                write!(f, "expected a {expected:?} image, got {actual:?}")
+            }
+            Self::Mismatch(diff) => write!(
+                f,
+                "{} pixels differ, by up to {}",
+                diff.differing_pixels, diff.max_difference
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GoldenError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// compare two images channel by channel, `None` if their sizes differ.
+pub fn diff_images(expected: &ColorImage, actual: &ColorImage, tolerance: u8) -> Option<ImageDiff> {
+    if expected.size != actual.size {
+        return None;
+    }
+
+    let mut diff = ImageDiff {
+        differing_pixels: 0,
+        max_difference: 0,
+    };
+
+    for (a, b) in expected.pixels.iter().zip(&actual.pixels) {
+        let difference = a
+            .to_array()
+            .iter()
+            .zip(b.to_array())
+            .map(|(a, b)| a.abs_diff(b))
+            .max()
+            .unwrap_or(0);
+
+        diff.max_difference = diff.max_difference.max(difference);
+
+        if difference > tolerance {
+            diff.differing_pixels += 1;
+        }
+    }
+
+    Some(diff)
+}
+
+/// check `image` against the golden image at `path`, allowing each channel to be off by `tolerance`.
+///
+/// with [`BLESS_ENV`] set, `image` becomes the new golden image instead.
+/// if they don't match, `image` is written next to the golden one as `*.actual.pam` so you can look at both.
+///
+/// # Errors
+/// if the images don't match, or the golden image can't be read.
+pub fn compare_golden(
+    image: &ColorImage,
+    path: impl AsRef<Path>,
+    tolerance: u8,
+) -> Result<(), GoldenError> {
+    let path = path.as_ref();
+
+    if std::env::var_os(BLESS_ENV).is_some() {
+        return Ok(write_image(image, path)?);
+    }
+
+    let expected = match read_image(path) {
+        Ok(expected) => expected,
+        Err(GoldenError::Io(err)) if err.kind() == io::ErrorKind::NotFound => {
+            write_image(image, &path.with_extension("actual.pam"))?;
+            return Err(GoldenError::Missing(path.to_owned()));
+        }
+        Err(err) => return Err(err),
+    };
+
+    let result = match diff_images(&expected, image, tolerance) {
+        None => Err(GoldenError::SizeMismatch {
+            expected: expected.size,
+            actual: image.size,
+        }),
+        Some(diff) if diff.differing_pixels > 0 => Err(GoldenError::Mismatch(diff)),
+        Some(_) => Ok(()),
+    };
+
+    if result.is_err() {
+        write_image(image, &path.with_extension("actual.pam"))?;
+    }
+
+    result
+}
+
+/// write an image as a pam file, which most image viewers open.
+///
+/// the pixels are stored as they are, premultiplied.
+///
+/// # Errors
+/// if the file can't be written.
+pub fn write_image(image: &ColorImage, path: &Path) -> io::Result<()> {
+    let mut file = io::BufWriter::new(fs::File::create(path)?);
+
+    write!(
+        file,
+        "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+        image.size[0], image.size[1]
+    )?;
+
+    for pixel in &image.pixels {
+        file.write_all(&pixel.to_array())?;
+    }
+
+    file.flush()
+}
+
+/// read an image written by [`write_image`].
+///
+/// # Errors
+/// if the file can't be read, or isn't a pam file like we write them.
+pub fn read_image(path: &Path) -> Result<ColorImage, GoldenError> {
+    let data = fs::read(path)?;
+    let format = || GoldenError::Format(path.to_owned());
+
+    let header_end = data
+        .windows(7)
+        .position(|window| window == b"ENDHDR\n")
+        .ok_or_else(format)?;
+    let header = std::str::from_utf8(&data[..header_end]).map_err(|_| format())?;
+    let pixels = &data[header_end + 7..];
+
+    let mut lines = header.lines();
+    if lines.next() != Some("P7") {
+        return Err(format());
+    }
+
+    let (mut width, mut height) = (None, None);
+    for line in lines {
+        match line.split_once(' ') {
+            Some(("WIDTH", value)) => width = value.parse::<usize>().ok(),
+            Some(("HEIGHT", value)) => height = value.parse::<usize>().ok(),
+            Some(("DEPTH", "4") | ("MAXVAL", "255") | ("TUPLTYPE", "RGB_ALPHA")) => {}
+            _ => return Err(format()),
+        }
+    }
+
+    let size = [width.ok_or_else(format)?, height.ok_or_else(format)?];
+    if pixels.len() != size[0] * size[1] * 4 {
+        return Err(format());
+    }
+
+    Ok(ColorImage {
+        size,
+        pixels: pixels
+            .chunks_exact(4)
+            .map(|p| Color32::from_rgba_premultiplied(p[0], p[1], p[2], p[3]))
+            .collect(),
+    })
+}
@@ -0,0 +1,166 @@
+use egui::ImeEvent;
+
+/// the ime messages we care about, with whatever we read from the input context.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImeMessage {
+    /// `WM_IME_STARTCOMPOSITION`
+    StartComposition,
+    /// `WM_IME_COMPOSITION`. `result` is what was committed (`GCS_RESULTSTR`),
+    /// `preedit` what is being composed right now (`GCS_COMPSTR`), either can be missing.
+    Composition {
+        result: Option<String>,
+        preedit: Option<String>,
+    },
+    /// `WM_IME_ENDCOMPOSITION`
+    EndComposition,
+}
+
+/// turns ime messages into what egui expects: `Enabled`, any number of `Preedit`s and `Commit`s, `Disabled`.
+#[derive(Default)]
+pub struct ImeState {
+    composing: bool,
+    // there's preedit text egui is showing, that still has to go away if the composition is cancelled
+    preedit_shown: bool,
+}
+
+impl ImeState {
+    pub fn translate(&mut self, message: ImeMessage) -> Vec<ImeEvent> {
+        let mut events = Vec::new();
+
+        match message {
+            ImeMessage::StartComposition => self.enable(&mut events),
+            ImeMessage::Composition { result, preedit } => {
+                // some imes skip WM_IME_STARTCOMPOSITION, egui still needs to know.
+                self.enable(&mut events);
+
+                // an empty result commits nothing, egui would still end the composition on it.
+                if let Some(result) = result.filter(|result| !result.is_empty()) {
+                    events.push(ImeEvent::Commit(result));
+                    self.preedit_shown = false;
+                }
+
+                // korean imes commit and start composing the next character in the same message.
+                if let Some(preedit) = preedit {
+                    self.preedit_shown = !preedit.is_empty();
+                    events.push(ImeEvent::Preedit(preedit));
+                }
+            }
+            ImeMessage::EndComposition => {
+                if self.composing {
+                    if self.preedit_shown {
+                        // cancelled, get rid of whatever egui shows.
+                        events.push(ImeEvent::Preedit(String::new()));
+                        self.preedit_shown = false;
+                    }
+
+                    events.push(ImeEvent::Disabled);
+                    self.composing = false;
+                }
+            }
+        }
+
+        events
+    }
+
+    fn enable(&mut self, events: &mut Vec<ImeEvent>) {
+        if !self.composing {
+            self.composing = true;
+            events.push(ImeEvent::Enabled);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn composition(result: Option<&str>, preedit: Option<&str>) -> ImeMessage {
+        ImeMessage::Composition {
+            result: result.map(str::to_owned),
+            preedit: preedit.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn start_update_commit() {
+        let mut ime = ImeState::default();
+
+        assert_eq!(
+            ime.translate(ImeMessage::StartComposition),
+            [ImeEvent::Enabled]
+        );
+        assert_eq!(
+            ime.translate(composition(None, Some("k"))),
+            [ImeEvent::Preedit("k".into())]
+        );
+        assert_eq!(
+            ime.translate(composition(None, Some("か"))),
+            [ImeEvent::Preedit("か".into())]
+        );
+        assert_eq!(
+            ime.translate(composition(Some("蚊"), None)),
+            [ImeEvent::Commit("蚊".into())]
+        );
+        // nothing left to clear up.
+        assert_eq!(
+            ime.translate(ImeMessage::EndComposition),
+            [ImeEvent::Disabled]
+        );
+        assert_eq!(ime.translate(ImeMessage::EndComposition), []);
+    }
+
+    #[test]
+    fn commit_and_keep_composing() {
+        let mut ime = ImeState::default();
+
+        // no WM_IME_STARTCOMPOSITION at all.
+        assert_eq!(
+            ime.translate(composition(Some("한"), Some("ㄱ"))),
+            [
+                ImeEvent::Enabled,
+                ImeEvent::Commit("한".into()),
+                ImeEvent::Preedit("ㄱ".into()),
+            ]
+        );
+        assert_eq!(
+            ime.translate(ImeMessage::EndComposition),
+            [ImeEvent::Preedit(String::new()), ImeEvent::Disabled]
+        );
+    }
+
+    #[test]
+    fn cancel() {
+        let mut ime = ImeState::default();
+
+        ime.translate(ImeMessage::StartComposition);
+        ime.translate(composition(None, Some("ni")));
+        assert_eq!(
+            ime.translate(ImeMessage::EndComposition),
+            [ImeEvent::Preedit(String::new()), ImeEvent::Disabled]
+        );
+
+        // the preedit was already cleared by the ime itself.
+        ime.translate(ImeMessage::StartComposition);
+        ime.translate(composition(None, Some("ni")));
+        ime.translate(composition(None, Some("")));
+        assert_eq!(
+            ime.translate(ImeMessage::EndComposition),
+            [ImeEvent::Disabled]
+        );
+    }
+
+    #[test]
+    fn empty_commit() {
+        let mut ime = ImeState::default();
+
+        ime.translate(ImeMessage::StartComposition);
+        ime.translate(composition(None, Some("ni")));
+        assert_eq!(ime.translate(composition(Some(""), None)), []);
+
+        // still showing the preedit, so it goes away at the end.
+        assert_eq!(
+            ime.translate(ImeMessage::EndComposition),
+            [ImeEvent::Preedit(String::new()), ImeEvent::Disabled]
+        );
+    }
+}
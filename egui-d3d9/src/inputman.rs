@@ -1,35 +1,105 @@
 #![allow(dead_code)]
 use std::time::Instant;
 
-use egui::{Event, Key, Modifiers, PointerButton, Pos2, RawInput, Rect, Vec2};
+use egui::{
+    DroppedFile, Event, ImeEvent, Key, PointerButton, Pos2, RawInput, Rect, TouchPhase, Vec2,
+    ViewportId,
+};
 use windows::Win32::{
-        Foundation::{HWND, RECT},
-        System::SystemServices::{MK_CONTROL, MK_SHIFT},
-        UI::{
-            Input::KeyboardAndMouse::{
-                GetAsyncKeyState, VIRTUAL_KEY, VK_BACK, VK_CONTROL, VK_DELETE, VK_DOWN, VK_END,
-                VK_ESCAPE, VK_HOME, VK_INSERT, VK_LEFT, VK_LSHIFT, VK_NEXT, VK_PRIOR, VK_RETURN,
-                VK_RIGHT, VK_SPACE, VK_TAB, VK_UP,
-            },
-            WindowsAndMessaging::{
-                GetClientRect, KF_REPEAT, WHEEL_DELTA, WM_CHAR, WM_KEYDOWN, WM_KEYUP,
-                WM_LBUTTONDBLCLK, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDBLCLK, WM_MBUTTONDOWN,
-                WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_RBUTTONDBLCLK,
-                WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SYSKEYDOWN, WM_SYSKEYUP, WM_XBUTTONDBLCLK,
-                WM_XBUTTONDOWN, WM_XBUTTONUP, XBUTTON1, XBUTTON2,
-            },
+    Foundation::{HWND, POINT, RECT},
+    Graphics::Gdi::ScreenToClient,
+    UI::{
+        Controls::WM_MOUSELEAVE,
+        HiDpi::GetDpiForWindow,
+        Input::Ime::{
+            ImmGetCompositionStringW, ImmGetContext, ImmReleaseContext, GCS_COMPSTR, GCS_RESULTSTR,
+            HIMC, IME_COMPOSITION_STRING,
         },
-    };
-
-use crate::get_clipboard_text;
+        Input::KeyboardAndMouse::{
+            GetKeyState, MapVirtualKeyW, TrackMouseEvent, MAPVK_VK_TO_CHAR, TME_LEAVE,
+            TRACKMOUSEEVENT,
+        },
+        Input::Pointer::{
+            GetPointerPenInfo, GetPointerTouchInfo, GetPointerType, POINTER_FLAGS,
+            POINTER_FLAG_CANCELED, POINTER_FLAG_INCONTACT, POINTER_PEN_INFO, POINTER_TOUCH_INFO,
+        },
+        Input::{
+            GetRawInputData, HRAWINPUT, MOUSE_MOVE_ABSOLUTE, RAWINPUT, RAWINPUTHEADER, RAWMOUSE,
+            RID_INPUT, RIM_TYPEMOUSE,
+        },
+        Shell::HDROP,
+        WindowsAndMessaging::{
+            GetAncestor, GetClientRect, GetForegroundWindow, GetMessageExtraInfo, GetMessageTime,
+            SystemParametersInfoW, GA_ROOT, KF_REPEAT, POINTER_INPUT_TYPE, PT_PEN, PT_TOUCH,
+            SPI_GETWHEELSCROLLCHARS, SPI_GETWHEELSCROLLLINES, SYSTEM_PARAMETERS_INFO_ACTION,
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS, UNICODE_NOCHAR, USER_DEFAULT_SCREEN_DPI,
+            WA_INACTIVE, WM_ACTIVATE, WM_CAPTURECHANGED, WM_CHAR, WM_DPICHANGED, WM_DROPFILES,
+            WM_IME_CHAR, WM_IME_COMPOSITION, WM_IME_ENDCOMPOSITION, WM_IME_STARTCOMPOSITION,
+            WM_INPUT, WM_KEYDOWN, WM_KEYUP, WM_KILLFOCUS, WM_LBUTTONDBLCLK, WM_LBUTTONDOWN,
+            WM_LBUTTONUP, WM_MBUTTONDBLCLK, WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEHWHEEL,
+            WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_POINTERCAPTURECHANGED, WM_POINTERDOWN, WM_POINTERUP,
+            WM_POINTERUPDATE, WM_RBUTTONDBLCLK, WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SETFOCUS,
+            WM_SYSKEYDOWN, WM_SYSKEYUP, WM_UNICHAR, WM_XBUTTONDBLCLK, WM_XBUTTONDOWN, WM_XBUTTONUP,
+            XBUTTON1, XBUTTON2,
+        },
+    },
+};
+
+use crate::{
+    ime::{ImeMessage, ImeState},
+    keys::{self, ModifierState, Utf16Decoder},
+    platform,
+    rawinput::{self, VirtualCursor},
+    touch::{self, Contact, TouchState},
+    wheel::{WheelConfig, WheelState},
+};
 
 pub struct InputManager {
     hwnd: HWND,
     events: Vec<Event>,
-    modifiers: Option<Modifiers>,
+    modifiers: ModifierState,
+    ime: ImeState,
+    // text we already have from the ime or WM_UNICHAR, which DefWindowProc might send us again as WM_CHAR
+    text_echo: String,
+    utf16: Utf16Decoder,
+    // take the mouse from WM_INPUT and move our own cursor, see `set_raw_input`
+    raw_input: bool,
+    cursor: VirtualCursor,
+    wheel: WheelState,
+    wheel_config: WheelConfig,
+    // only with drag and drop turned on, see `set_accept_files`
+    accept_files: bool,
+    dropped_files: Vec<DroppedFile>,
+    touch: TouchState,
+    // touch and pen come as WM_POINTER once we got one, no need for the mouse windows makes up
+    touch_seen: bool,
+    // buttons that went down in our window and haven't come up yet
+    held_buttons: Vec<PointerButton>,
+    // we took the capture ourselves, so it's ours to release
+    captured: bool,
+    // (re)leasing the capture sends WM_CAPTURECHANGED right away, whoever feeds us does it once we return
+    capture_request: Option<bool>,
+    // where the mouse was last, in points, for releases windows doesn't tell us about
+    last_pos: Pos2,
+    // whether we asked for the next WM_MOUSELEAVE already
+    tracking_leave: bool,
+    focused: bool,
+    // keys egui saw go down, and their physical keys, to let go of when we lose focus
+    held_keys: Vec<(Key, Option<Key>)>,
     start: Instant,
+    // window dpi / 96
+    dpi_scale: f32,
+    // replaces `dpi_scale` if set
+    pixels_per_point_override: Option<f32>,
+    // user controlled, on top of everything else
+    ui_scale: f32,
+    // what egui used last frame, mouse positions are converted with this
+    pixels_per_point: f32,
 }
 
+// only the hwnd is in the way, and window handles are fine to use from any thread.
+unsafe impl Send for InputManager {}
+
 /// High-level overview of recognized `WndProc` messages.
 #[repr(u8)]
 pub enum InputResult {
@@ -38,10 +108,17 @@ pub enum InputResult {
     MouseLeft,
     MouseRight,
     MouseMiddle,
+    /// touch or pen, from `WM_POINTER*`.
+    Touch,
     Character,
     Scroll,
     Zoom,
     Key,
+    Ime,
+    Drop,
+    MouseLeave,
+    Focus,
+    Dpi,
 }
 
 impl InputResult {
@@ -54,192 +131,260 @@ impl InputResult {
     pub const fn is_unknown(&self) -> bool {
         matches!(*self, Self::Unknown)
     }
+
+    #[inline]
+    pub const fn is_pointer(&self) -> bool {
+        matches!(
+            *self,
+            Self::MouseMove
+                | Self::MouseLeft
+                | Self::MouseRight
+                | Self::MouseMiddle
+                | Self::Touch
+                | Self::Scroll
+                | Self::Zoom
+        )
+    }
+
+    #[inline]
+    pub const fn is_keyboard(&self) -> bool {
+        matches!(*self, Self::Character | Self::Key | Self::Ime)
+    }
 }
 
 impl InputManager {
     pub fn new(hwnd: HWND) -> Self {
+        let dpi_scale = get_dpi_scale(unsafe { GetDpiForWindow(hwnd) });
+
         Self {
             hwnd,
             events: vec![],
-            modifiers: None,
-            start: Instant::now()
+            modifiers: ModifierState::default(),
+            ime: ImeState::default(),
+            text_echo: String::new(),
+            utf16: Utf16Decoder::default(),
+            raw_input: false,
+            cursor: VirtualCursor::default(),
+            wheel: WheelState::default(),
+            wheel_config: WheelConfig::default(),
+            accept_files: false,
+            dropped_files: vec![],
+            touch: TouchState::default(),
+            touch_seen: false,
+            held_buttons: vec![],
+            captured: false,
+            capture_request: None,
+            last_pos: Pos2::ZERO,
+            tracking_leave: false,
+            focused: unsafe { GetForegroundWindow() == GetAncestor(hwnd, GA_ROOT) },
+            held_keys: vec![],
+            start: Instant::now(),
+            dpi_scale,
+            pixels_per_point_override: None,
+            ui_scale: 1.,
+            pixels_per_point: dpi_scale,
         }
     }
 
+    pub fn set_pixels_per_point_override(&mut self, pixels_per_point: Option<f32>) {
+        self.pixels_per_point_override = pixels_per_point;
+    }
+
+    pub fn set_ui_scale(&mut self, scale: f32) {
+        self.ui_scale = scale;
+    }
+
+    pub const fn ui_scale(&self) -> f32 {
+        self.ui_scale
+    }
+
+    /// what we tell egui the os wants, before egui's own zoom.
+    pub fn native_pixels_per_point(&self) -> f32 {
+        self.pixels_per_point_override.unwrap_or(self.dpi_scale) * self.ui_scale
+    }
+
+    /// take the mouse from `WM_INPUT` instead of the regular mouse messages, which we then ignore.
+    ///
+    /// we move a cursor of our own with the deltas, for games that keep the real one clipped or centered.
+    /// absolute raw input, e.g. from tablets or remote desktop, doesn't move it.
+    pub fn set_raw_input(&mut self, raw_input: bool) {
+        self.raw_input = raw_input;
+    }
+
+    pub const fn is_raw_input(&self) -> bool {
+        self.raw_input
+    }
+
+    /// take files dragged onto the window.
+    ///
+    /// `WM_DROPFILES` only says what was dropped, never what is being dragged, so `hovered_files` stays empty.
+    /// the window has to be told as well, see [`platform::accept_files`].
+    pub fn set_accept_files(&mut self, accept: bool) {
+        self.accept_files = accept;
+    }
+
+    pub const fn accepts_files(&self) -> bool {
+        self.accept_files
+    }
+
+    pub const fn hwnd(&self) -> HWND {
+        self.hwnd
+    }
+
+    /// whether to grab (`true`) or release the mouse since the last call, see [`platform::set_capture`].
+    ///
+    /// we only grab it if egui wants the pointer and the game doesn't hold it already,
+    /// and only ever release a capture we took.
+    pub fn take_capture_request(&mut self, wants_pointer: bool) -> Option<bool> {
+        match self.capture_request.take()? {
+            true if !wants_pointer || platform::has_capture(self.hwnd) => None,
+            true => {
+                self.captured = true;
+                Some(true)
+            }
+            false => Some(false),
+        }
+    }
+
+    /// where our own cursor is in raw input mode, in points.
+    pub fn virtual_cursor(&self) -> Option<Pos2> {
+        if self.raw_input {
+            Some(self.cursor.pos()? / self.pixels_per_point)
+        } else {
+            None
+        }
+    }
+
+    /// mouse position in points.
+    #[inline]
+    fn get_pos(&self, lparam: isize) -> Pos2 {
+        get_pos(lparam) / self.pixels_per_point
+    }
+
     #[allow(clippy::too_many_lines)]
     pub fn process(&mut self, umsg: u32, wparam: usize, lparam: isize) -> InputResult {
         let w_high = (wparam >> 16) as u16;
 
+        if let Some(result) = mouse_message_result(umsg) {
+            // in raw input mode the mouse comes from WM_INPUT, touch and pen from their own messages.
+            // still report what they are, the game shouldn't see them either while we block input.
+            if self.raw_input
+                || (self.touch_seen && touch::is_emulated_mouse(unsafe { GetMessageExtraInfo() }.0))
+            {
+                return result;
+            }
+        }
+
         match umsg {
+            WM_INPUT if self.raw_input => self.process_raw_input(lparam),
+            WM_POINTERDOWN | WM_POINTERUPDATE | WM_POINTERUP | WM_POINTERCAPTURECHANGED => {
+                self.process_pointer(umsg, wparam)
+            }
+            WM_DROPFILES if self.accept_files => {
+                let files = platform::take_dropped_files(HDROP(wparam as _));
+                self.dropped_files.extend(files);
+                InputResult::Drop
+            }
             WM_MOUSEMOVE => {
-                self.alter_modifiers(get_mouse_modifiers(wparam));
-
-                self.events.push(Event::PointerMoved(get_pos(lparam)));
+                self.track_leave();
+                self.last_pos = self.get_pos(lparam);
+                self.events.push(Event::PointerMoved(self.last_pos));
                 InputResult::MouseMove
             }
+            // in raw input mode, our cursor never leaves.
+            WM_MOUSELEAVE if !self.raw_input => {
+                self.tracking_leave = false;
+                self.events.push(Event::PointerGone);
+                InputResult::MouseLeave
+            }
             WM_LBUTTONDOWN | WM_LBUTTONDBLCLK => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: PointerButton::Primary,
-                    pressed: true,
-                    modifiers,
-                });
+                self.push_button(lparam, PointerButton::Primary, true);
                 InputResult::MouseLeft
             }
             WM_LBUTTONUP => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: PointerButton::Primary,
-                    pressed: false,
-                    modifiers,
-                });
+                self.push_button(lparam, PointerButton::Primary, false);
                 InputResult::MouseLeft
             }
             WM_RBUTTONDOWN | WM_RBUTTONDBLCLK => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: PointerButton::Secondary,
-                    pressed: true,
-                    modifiers,
-                });
+                self.push_button(lparam, PointerButton::Secondary, true);
                 InputResult::MouseRight
             }
             WM_RBUTTONUP => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: PointerButton::Secondary,
-                    pressed: false,
-                    modifiers,
-                });
+                self.push_button(lparam, PointerButton::Secondary, false);
                 InputResult::MouseRight
             }
             WM_MBUTTONDOWN | WM_MBUTTONDBLCLK => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: PointerButton::Middle,
-                    pressed: true,
-                    modifiers,
-                });
+                self.push_button(lparam, PointerButton::Middle, true);
                 InputResult::MouseMiddle
             }
             WM_MBUTTONUP => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: PointerButton::Middle,
-                    pressed: false,
-                    modifiers,
-                });
+                self.push_button(lparam, PointerButton::Middle, false);
                 InputResult::MouseMiddle
             }
             WM_XBUTTONDOWN | WM_XBUTTONDBLCLK => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: if w_high & XBUTTON1 != 0 {
-                        PointerButton::Extra1
-                    } else if w_high & XBUTTON2 != 0 {
-                        PointerButton::Extra2
-                    } else {
-                        unreachable!()
-                    },
-                    pressed: true,
-                    modifiers,
-                });
+                let Some(button) = get_x_button(w_high) else {
+                    return InputResult::Unknown;
+                };
+
+                self.push_button(lparam, button, true);
                 InputResult::MouseMiddle
             }
             WM_XBUTTONUP => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: if w_high & XBUTTON1 != 0 {
-                        PointerButton::Extra1
-                    } else if w_high & XBUTTON2 != 0 {
-                        PointerButton::Extra2
-                    } else {
-                        unreachable!()
-                    },
-                    pressed: false,
-                    modifiers,
-                });
+                let Some(button) = get_x_button(w_high) else {
+                    return InputResult::Unknown;
+                };
+
+                self.push_button(lparam, button, false);
                 InputResult::MouseMiddle
             }
+            WM_CAPTURECHANGED => {
+                // someone else took the mouse from us or the game, or the window lost it while a button was down.
+                // we won't see the button go up, so egui has to let go now.
+                if HWND(lparam as _) != self.hwnd {
+                    self.release_capture(false);
+                }
+                InputResult::Focus
+            }
             WM_CHAR => {
-                if let Some(ch) = char::from_u32(wparam as _) {
-                    if !ch.is_control() {
-                        self.events.push(Event::Text(ch.into()));
+                if let Some(ch) = self.utf16.push(wparam as u16) {
+                    if let Some(rest) = self.text_echo.strip_prefix(ch) {
+                        // we already got this one.
+                        self.text_echo = rest.to_owned();
+                    } else {
+                        self.push_text(ch);
                     }
                 }
                 InputResult::Character
             }
-            WM_MOUSEWHEEL => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                let delta = w_high as i16 as f32 * 10. / WHEEL_DELTA as f32;
-
-                if wparam & MK_CONTROL.0 as usize != 0 {
-                    self.events
-                        .push(Event::Zoom(if delta > 0. { 1.5 } else { 0.5 }));
-                    InputResult::Zoom
-                } else {
-                    self.events.push(Event::MouseWheel {
-                        delta: Vec2::new(0., delta),
-                        unit: egui::MouseWheelUnit::Point,
-                        modifiers,
-                    });
-                    InputResult::Scroll
-                }
-            }
-            WM_MOUSEHWHEEL => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                let delta = w_high as i16 as f32 * 10. / WHEEL_DELTA as f32;
-
-                if wparam & MK_CONTROL.0 as usize != 0 {
-                    self.events
-                        .push(Event::Zoom(if delta > 0. { 1.5 } else { 0.5 }));
-                    InputResult::Zoom
-                } else {
-                    self.events.push(Event::MouseWheel {
-                        delta: Vec2::new(delta, 0.),
-                        unit: egui::MouseWheelUnit::Point,
-                        modifiers,
-                    });
-                    InputResult::Scroll
+            WM_UNICHAR => {
+                // UNICODE_NOCHAR only asks whether we take these, the caller answers that.
+                if wparam as u32 != UNICODE_NOCHAR {
+                    if let Some(ch) = char::from_u32(wparam as _) {
+                        self.text_echo.push(ch);
+                        self.push_text(ch);
+                    }
                 }
+                InputResult::Character
             }
-            msg @ (WM_KEYDOWN | WM_SYSKEYDOWN) => {
-                let modifiers = get_key_modifiers(msg);
-                self.modifiers = Some(modifiers);
+            WM_MOUSEWHEEL => self.push_wheel(w_high as i16, false),
+            WM_MOUSEHWHEEL => self.push_wheel(w_high as i16, true),
+            WM_KEYDOWN | WM_SYSKEYDOWN => {
+                // anything the ime would echo arrives before the next key.
+                self.text_echo.clear();
+
+                let (scancode, extended) = keys::scancode_from_lparam(lparam);
+                let time = unsafe { GetMessageTime() } as u32;
+                self.modifiers
+                    .update(wparam as u16, scancode, extended, true, time);
+                let modifiers = self.modifiers.modifiers();
 
                 if let Some(key) = get_key(wparam) {
+                    // the repeat flag lives in the high word, with the scan code.
+                    let repeat = (lparam >> 16) as u32 & KF_REPEAT != 0;
+
                     if key == Key::V && modifiers.ctrl {
-                        if let Ok(clipboard) = get_clipboard_text() {
-                            self.events.push(Event::Text(clipboard));
-                        }
+                        // filled in from the clipboard once egui runs, see `EguiDx9::run_frame`.
+                        self.events.push(Event::Paste(String::new()));
                     }
 
                     if key == Key::C && modifiers.ctrl {
@@ -250,56 +395,412 @@ impl InputManager {
                         self.events.push(Event::Cut);
                     }
 
+                    let physical_key = get_physical_key(lparam);
+                    if !self.held_keys.iter().any(|(held, _)| *held == key) {
+                        self.held_keys.push((key, physical_key));
+                    }
+
                     self.events.push(Event::Key {
                         pressed: true,
                         modifiers,
                         key,
-                        repeat: lparam & (KF_REPEAT as isize) > 0,
-                        physical_key: None,
+                        repeat,
+                        physical_key,
                     });
                 }
                 InputResult::Key
             }
-            msg @ (WM_KEYUP | WM_SYSKEYUP) => {
-                let modifiers = get_key_modifiers(msg);
-                self.modifiers = Some(modifiers);
+            WM_KEYUP | WM_SYSKEYUP => {
+                let (scancode, extended) = keys::scancode_from_lparam(lparam);
+                let time = unsafe { GetMessageTime() } as u32;
+                self.modifiers
+                    .update(wparam as u16, scancode, extended, false, time);
+                let modifiers = self.modifiers.modifiers();
 
                 if let Some(key) = get_key(wparam) {
+                    self.held_keys.retain(|(held, _)| *held != key);
+
                     self.events.push(Event::Key {
                         pressed: false,
                         modifiers,
                         key,
                         repeat: false,
-                        physical_key: None,
+                        physical_key: get_physical_key(lparam),
                     });
                 }
                 InputResult::Key
             }
+            WM_IME_STARTCOMPOSITION => {
+                self.process_ime(ImeMessage::StartComposition);
+                InputResult::Ime
+            }
+            WM_IME_COMPOSITION => {
+                let flags = lparam as u32;
+                let (result, preedit) = self.read_composition(flags);
+
+                self.process_ime(ImeMessage::Composition { result, preedit });
+                InputResult::Ime
+            }
+            WM_IME_ENDCOMPOSITION => {
+                self.process_ime(ImeMessage::EndComposition);
+                InputResult::Ime
+            }
+            // we took the text straight from the composition already.
+            WM_IME_CHAR => InputResult::Ime,
+            WM_SETFOCUS => {
+                self.set_focused(true);
+                InputResult::Focus
+            }
+            WM_KILLFOCUS => {
+                self.set_focused(false);
+                InputResult::Focus
+            }
+            WM_ACTIVATE => {
+                self.set_focused(wparam as u16 as u32 != WA_INACTIVE);
+                InputResult::Focus
+            }
+            WM_DPICHANGED => {
+                // both axes always have the same dpi.
+                self.dpi_scale = get_dpi_scale(wparam as u16 as u32);
+                InputResult::Dpi
+            }
             _ => InputResult::Unknown,
         }
     }
 
-    fn alter_modifiers(&mut self, new: Modifiers) {
-        if let Some(old) = self.modifiers.as_mut() {
-            *old = new;
+    /// touch and pen, the mouse and touchpads keep going through the regular mouse messages.
+    fn process_pointer(&mut self, umsg: u32, wparam: usize) -> InputResult {
+        let id = wparam as u16 as u32;
+
+        let mut kind = POINTER_INPUT_TYPE::default();
+        if unsafe { GetPointerType(id, &mut kind) }.is_err() {
+            return InputResult::Unknown;
+        }
+
+        let (info, force) = match kind {
+            PT_TOUCH => {
+                let mut info = POINTER_TOUCH_INFO::default();
+                if unsafe { GetPointerTouchInfo(id, &mut info) }.is_err() {
+                    return InputResult::Unknown;
+                }
+
+                // TOUCH_MASK_PRESSURE
+                let force = (info.touchMask & 0x4 != 0).then(|| info.pressure as f32 / 1024.);
+                (info.pointerInfo, force)
+            }
+            PT_PEN => {
+                let mut info = POINTER_PEN_INFO::default();
+                if unsafe { GetPointerPenInfo(id, &mut info) }.is_err() {
+                    return InputResult::Unknown;
+                }
+
+                // PEN_MASK_PRESSURE
+                let force = (info.penMask & 0x1 != 0).then(|| info.pressure as f32 / 1024.);
+                (info.pointerInfo, force)
+            }
+            _ => return InputResult::Unknown,
+        };
+
+        self.touch_seen = true;
+
+        let flags = info.pointerFlags;
+        let phase = if umsg == WM_POINTERCAPTURECHANGED || has_flag(flags, POINTER_FLAG_CANCELED) {
+            TouchPhase::Cancel
+        } else if umsg == WM_POINTERDOWN {
+            TouchPhase::Start
+        } else if umsg == WM_POINTERUP {
+            TouchPhase::End
+        } else if has_flag(flags, POINTER_FLAG_INCONTACT) {
+            TouchPhase::Move
+        } else {
+            // a pen hovering over the screen.
+            return InputResult::Touch;
+        };
+
+        let screen = info.ptPixelLocation;
+        let pos = self.client_pos(screen.x as f32, screen.y as f32);
+
+        self.push_contact(Contact {
+            device: info.sourceDevice.0 as u64,
+            id: id.into(),
+            phase,
+            pos,
+            force,
+        });
+        InputResult::Touch
+    }
+
+    fn push_contact(&mut self, contact: Contact) {
+        let events = self.touch.translate(contact, self.modifiers.modifiers());
+        self.events.extend(events);
+    }
+
+    /// a position on the screen, in pixels, to points in our window.
+    fn client_pos(&self, x: f32, y: f32) -> Pos2 {
+        let mut origin = POINT::default();
+        unsafe {
+            let _ = ScreenToClient(self.hwnd, &mut origin);
+        }
+
+        // `origin` is now where the screen's corner is, relative to the window.
+        Pos2::new(x + origin.x as f32, y + origin.y as f32) / self.pixels_per_point
+    }
+
+    /// a regular mouse button message. we ask to hold on to the mouse while any button is down,
+    /// so drags keep working when the cursor leaves the window.
+    fn push_button(&mut self, lparam: isize, button: PointerButton, pressed: bool) {
+        let pos = self.get_pos(lparam);
+        self.last_pos = pos;
+
+        self.events.push(Event::PointerButton {
+            pos,
+            button,
+            pressed,
+            modifiers: self.modifiers.modifiers(),
+        });
+
+        if pressed {
+            if !self.held_buttons.contains(&button) {
+                self.held_buttons.push(button);
+            }
+
+            if !self.captured {
+                self.capture_request = Some(true);
+            }
+        } else {
+            self.held_buttons.retain(|held| *held != button);
+
+            if self.held_buttons.is_empty() {
+                self.release_capture(true);
+            }
         }
     }
 
-    pub fn collect_input(&mut self) -> RawInput {
+    /// let go of the held buttons. `release` gives the capture back if we took it, otherwise it's already gone.
+    fn release_capture(&mut self, release: bool) {
+        // before ReleaseCapture, which sends WM_CAPTURECHANGED right away.
+        if release && self.captured {
+            self.capture_request = Some(false);
+        }
+        self.captured = false;
+
+        let modifiers = self.modifiers.modifiers();
+        for button in std::mem::take(&mut self.held_buttons) {
+            self.events.push(Event::PointerButton {
+                pos: self.last_pos,
+                button,
+                pressed: false,
+                modifiers,
+            });
+        }
+    }
+
+    fn track_leave(&mut self) {
+        if self.tracking_leave {
+            return;
+        }
+
+        let mut track = TRACKMOUSEEVENT {
+            cbSize: std::mem::size_of::<TRACKMOUSEEVENT>() as u32,
+            dwFlags: TME_LEAVE,
+            hwndTrack: self.hwnd,
+            dwHoverTime: 0,
+        };
+        self.tracking_leave = unsafe { TrackMouseEvent(&mut track) }.is_ok();
+    }
+
+    /// both the focus and the activation messages end up here, egui only hears about actual changes.
+    fn set_focused(&mut self, focused: bool) {
+        if self.focused == focused {
+            return;
+        }
+        self.focused = focused;
+
+        if focused {
+            // whatever happened while we weren't looking, the os knows what's held now.
+            self.modifiers
+                .resync(|vk| unsafe { GetKeyState(vk as i32) } < 0);
+        } else {
+            // we won't see the key ups from here on.
+            self.modifiers = ModifierState::default();
+            self.utf16.reset();
+
+            let modifiers = self.modifiers.modifiers();
+            for (key, physical_key) in std::mem::take(&mut self.held_keys) {
+                self.events.push(Event::Key {
+                    key,
+                    physical_key,
+                    pressed: false,
+                    repeat: false,
+                    modifiers,
+                });
+            }
+        }
+
+        self.events.push(Event::WindowFocused(focused));
+    }
+
+    fn process_raw_input(&mut self, lparam: isize) -> InputResult {
+        let Some(mouse) = (unsafe { get_raw_mouse(lparam) }) else {
+            return InputResult::Unknown;
+        };
+        let Some(screen_size) = self.get_screen_size() else {
+            return InputResult::Unknown;
+        };
+
+        let mut result = InputResult::MouseMove;
+
+        let delta = Vec2::new(mouse.lLastX as f32, mouse.lLastY as f32);
+        let pos = if mouse.usFlags.0 & MOUSE_MOVE_ABSOLUTE.0 == 0 && delta != Vec2::ZERO {
+            let pos = self.cursor.move_by(delta, screen_size.to_vec2()) / self.pixels_per_point;
+            self.events.push(Event::PointerMoved(pos));
+            pos
+        } else {
+            let center = (screen_size.to_vec2() / 2.).to_pos2();
+            self.cursor.pos().unwrap_or(center) / self.pixels_per_point
+        };
+
+        // safe. mouse events always carry the button flags.
+        let (flags, data) = unsafe {
+            let buttons = mouse.Anonymous.Anonymous;
+            (buttons.usButtonFlags, buttons.usButtonData)
+        };
+
+        for (button, pressed) in rawinput::buttons(flags) {
+            self.events.push(Event::PointerButton {
+                pos,
+                button,
+                pressed,
+                modifiers: self.modifiers.modifiers(),
+            });
+
+            result = match button {
+                PointerButton::Primary => InputResult::MouseLeft,
+                PointerButton::Secondary => InputResult::MouseRight,
+                _ => InputResult::MouseMiddle,
+            };
+        }
+
+        if flags & rawinput::WHEEL != 0 {
+            result = self.push_wheel(data as i16, false);
+        }
+
+        if flags & rawinput::HWHEEL != 0 {
+            result = self.push_wheel(data as i16, true);
+        }
+
+        result
+    }
+
+    fn push_wheel(&mut self, delta: i16, horizontal: bool) -> InputResult {
+        if self
+            .wheel
+            .push(delta, horizontal, self.modifiers.modifiers())
+        {
+            InputResult::Zoom
+        } else {
+            InputResult::Scroll
+        }
+    }
+
+    pub fn set_wheel_config(&mut self, config: WheelConfig) {
+        self.wheel_config = config;
+    }
+
+    pub const fn wheel_config(&self) -> &WheelConfig {
+        &self.wheel_config
+    }
+
+    fn push_text(&mut self, ch: char) {
+        if !ch.is_control() {
+            self.events.push(Event::Text(ch.into()));
+        }
+    }
+
+    fn process_ime(&mut self, message: ImeMessage) {
+        for event in self.ime.translate(message) {
+            if let ImeEvent::Commit(text) = &event {
+                self.text_echo.push_str(text);
+            }
+
+            self.events.push(Event::Ime(event));
+        }
+    }
+
+    /// the committed and the in-progress text of the current composition, as far as `flags` says they changed.
+    fn read_composition(&self, flags: u32) -> (Option<String>, Option<String>) {
+        unsafe {
+            let himc = ImmGetContext(self.hwnd);
+            if himc.is_invalid() {
+                return (None, None);
+            }
+
+            let result = if flags & GCS_RESULTSTR.0 != 0 {
+                get_composition_string(himc, GCS_RESULTSTR)
+            } else {
+                None
+            };
+
+            let preedit = if flags & GCS_COMPSTR.0 != 0 {
+                get_composition_string(himc, GCS_COMPSTR)
+            } else if result.is_none() {
+                // neither changed, that's how the composition gets cleared.
+                Some(String::new())
+            } else {
+                None
+            };
+
+            let _ = ImmReleaseContext(self.hwnd, himc);
+
+            (result, preedit)
+        }
+    }
+
+    /// hand egui an event that didn't come from a window message.
+    pub fn push_event(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    pub fn has_events(&self) -> bool {
+        !self.events.is_empty() || !self.wheel.is_empty() || !self.dropped_files.is_empty()
+    }
+
+    /// `zoom_factor` is egui's own zoom on top of what we report, see [`egui::Context::zoom_factor`].
+    pub fn collect_input(&mut self, zoom_factor: f32) -> RawInput {
         let time = self.get_system_time();
-        RawInput {
-            modifiers: self.modifiers.unwrap_or_default(),
+        let native_pixels_per_point = self.native_pixels_per_point();
+        self.pixels_per_point = native_pixels_per_point * zoom_factor;
+
+        if !self.wheel.is_empty() {
+            let lines = [
+                get_scroll_setting(SPI_GETWHEELSCROLLCHARS),
+                get_scroll_setting(SPI_GETWHEELSCROLLLINES),
+            ];
+            let wheel = self.wheel.take(&self.wheel_config, lines);
+            self.events.extend(wheel);
+        }
+
+        let mut input = RawInput {
+            modifiers: self.modifiers.modifiers(),
             events: std::mem::take(&mut self.events),
-            screen_rect: Some(self.get_screen_rect()),
+            // egui keeps the last screen rect if we can't get a new one.
+            screen_rect: self.get_screen_rect(),
             time: Some(time),
-            //pixels_per_point: Some(1.),
             max_texture_side: None,
             predicted_dt: 1. / 60.,
             hovered_files: vec![],
-            dropped_files: vec![],
-            focused: true,
+            dropped_files: std::mem::take(&mut self.dropped_files),
+            focused: self.focused,
             ..Default::default()
-        }
+        };
+
+        input
+            .viewports
+            .entry(ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(native_pixels_per_point);
+
+        input
     }
 
     /// Returns time in seconds.
@@ -320,27 +821,80 @@ impl InputManager {
     }
 
     #[inline]
-    pub fn get_screen_size(&self) -> Pos2 {
+    pub fn get_screen_size(&self) -> Option<Pos2> {
         let mut rect = RECT::default();
         unsafe {
-            GetClientRect(self.hwnd, &mut rect).expect("Failed to GetClientRect()");
+            GetClientRect(self.hwnd, &mut rect).ok()?;
         }
 
-        Pos2::new(
+        Some(Pos2::new(
             (rect.right - rect.left) as f32,
             (rect.bottom - rect.top) as f32,
-        )
+        ))
     }
 
+    /// the client area in points.
     #[inline]
-    pub fn get_screen_rect(&self) -> Rect {
-        Rect {
+    pub fn get_screen_rect(&self) -> Option<Rect> {
+        Some(Rect {
             min: Pos2::ZERO,
-            max: self.get_screen_size(),
-        }
+            max: self.get_screen_size()? / self.pixels_per_point,
+        })
     }
 }
 
+/// what a regular mouse message would have been reported as.
+const fn mouse_message_result(umsg: u32) -> Option<InputResult> {
+    Some(match umsg {
+        WM_MOUSEMOVE => InputResult::MouseMove,
+        WM_LBUTTONDOWN | WM_LBUTTONDBLCLK | WM_LBUTTONUP => InputResult::MouseLeft,
+        WM_RBUTTONDOWN | WM_RBUTTONDBLCLK | WM_RBUTTONUP => InputResult::MouseRight,
+        WM_MBUTTONDOWN | WM_MBUTTONDBLCLK | WM_MBUTTONUP | WM_XBUTTONDOWN | WM_XBUTTONDBLCLK
+        | WM_XBUTTONUP => InputResult::MouseMiddle,
+        WM_MOUSEWHEEL | WM_MOUSEHWHEEL => InputResult::Scroll,
+        _ => return None,
+    })
+}
+
+unsafe fn get_raw_mouse(lparam: isize) -> Option<RAWMOUSE> {
+    let mut raw = RAWINPUT::default();
+    let mut size = std::mem::size_of::<RAWINPUT>() as u32;
+
+    let read = GetRawInputData(
+        HRAWINPUT(lparam as _),
+        RID_INPUT,
+        Some(std::ptr::addr_of_mut!(raw).cast()),
+        &mut size,
+        std::mem::size_of::<RAWINPUTHEADER>() as u32,
+    );
+
+    // hid devices can be bigger than what we read, but those aren't mice anyway.
+    if read == u32::MAX || raw.header.dwType != RIM_TYPEMOUSE.0 {
+        return None;
+    }
+
+    Some(raw.data.mouse)
+}
+
+/// lines or characters one wheel notch scrolls, as the user set it.
+fn get_scroll_setting(setting: SYSTEM_PARAMETERS_INFO_ACTION) -> u32 {
+    let mut lines = 3u32;
+    unsafe {
+        let _ = SystemParametersInfoW(
+            setting,
+            0,
+            Some(std::ptr::addr_of_mut!(lines).cast()),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        );
+    }
+
+    lines
+}
+
+const fn has_flag(flags: POINTER_FLAGS, flag: POINTER_FLAGS) -> bool {
+    flags.0 & flag.0 != 0
+}
+
 const fn get_pos(lparam: isize) -> Pos2 {
     let x = (lparam & 0xFFFF) as i16 as f32;
     let y = ((lparam >> 16) & 0xFFFF) as i16 as f32;
@@ -348,53 +902,58 @@ const fn get_pos(lparam: isize) -> Pos2 {
     Pos2::new(x, y)
 }
 
-const fn get_mouse_modifiers(wparam: usize) -> Modifiers {
-    Modifiers {
-        alt: false,
-        ctrl: (wparam & MK_CONTROL.0 as usize) != 0,
-        shift: (wparam & MK_SHIFT.0 as usize) != 0,
-        mac_cmd: false,
-        command: (wparam & MK_CONTROL.0 as usize) != 0,
+fn get_dpi_scale(dpi: u32) -> f32 {
+    // 0 means the call failed, just assume no scaling then.
+    if dpi == 0 {
+        1.
+    } else {
+        dpi as f32 / USER_DEFAULT_SCREEN_DPI as f32
     }
 }
 
-fn get_key_modifiers(msg: u32) -> Modifiers {
-    let ctrl = unsafe { GetAsyncKeyState(VK_CONTROL.0 as _) != 0 };
-    let shift = unsafe { GetAsyncKeyState(VK_LSHIFT.0 as _) != 0 };
-
-    Modifiers {
-        alt: msg == WM_SYSKEYDOWN,
-        mac_cmd: false,
-        command: ctrl,
-        shift,
-        ctrl,
+const fn get_x_button(w_high: u16) -> Option<PointerButton> {
+    if w_high & XBUTTON1 != 0 {
+        Some(PointerButton::Extra1)
+    } else if w_high & XBUTTON2 != 0 {
+        Some(PointerButton::Extra2)
+    } else {
+        None
     }
 }
 
 fn get_key(wparam: usize) -> Option<Key> {
-    Some(match wparam {
-        0x30..=0x39 => Key::ALL[(wparam as u8 - 0x1F) as usize],
-        0x41..=0x5A => Key::ALL[(wparam as u8 - 0x26) as usize],
-        0x70..=0x83 => Key::ALL[(wparam as u8 - 0x3b) as usize],
-        _ => match VIRTUAL_KEY(wparam as u16) {
-            VK_DOWN => Key::ArrowDown,
-            VK_LEFT => Key::ArrowLeft,
-            VK_RIGHT => Key::ArrowRight,
-            VK_UP => Key::ArrowUp,
-            VK_ESCAPE => Key::Escape,
-            VK_TAB => Key::Tab,
-            VK_BACK => Key::Backspace,
-            VK_RETURN => Key::Enter,
-            VK_SPACE => Key::Space,
-            VK_INSERT => Key::Insert,
-            VK_DELETE => Key::Delete,
-            VK_HOME => Key::Home,
-            VK_END => Key::End,
-            VK_PRIOR => Key::PageUp,
-            VK_NEXT => Key::PageDown,
-            _ => {
-                return None;
-            }
-        },
-    })
+    let vk = wparam as u16;
+
+    // the low word is the unshifted character, dead keys set the top bit.
+    // zero means the layout types nothing on this key.
+    let ch = unsafe { MapVirtualKeyW(vk as u32, MAPVK_VK_TO_CHAR) };
+    let layout_char = if ch & 0x8000_0000 == 0 && ch & 0xFFFF != 0 {
+        char::from_u32(ch & 0xFFFF)
+    } else {
+        None
+    };
+
+    keys::key_from_vk(vk, layout_char)
+}
+
+unsafe fn get_composition_string(himc: HIMC, kind: IME_COMPOSITION_STRING) -> Option<String> {
+    // the length is in bytes, of utf-16 without a terminator.
+    let len = ImmGetCompositionStringW(himc, kind, None, 0);
+    if len < 0 {
+        return None;
+    }
+
+    let mut buf = vec![0u16; len as usize / 2];
+    let len = ImmGetCompositionStringW(himc, kind, Some(buf.as_mut_ptr().cast()), len as u32);
+    if len < 0 {
+        return None;
+    }
+
+    buf.truncate(len as usize / 2);
+    Some(String::from_utf16_lossy(&buf))
+}
+
+fn get_physical_key(lparam: isize) -> Option<Key> {
+    let (scancode, extended) = keys::scancode_from_lparam(lparam);
+    keys::physical_key(scancode, extended)
 }
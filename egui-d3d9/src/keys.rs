@@ -0,0 +1,699 @@
+use egui::{Key, Modifiers};
+
+// virtual keys that map straight to an egui key, no matter the layout.
+// media and browser keys have no egui counterpart, so they're missing on purpose.
+const VIRTUAL_KEYS: &[(u16, Key)] = &[
+    (0x08, Key::Backspace),  // VK_BACK
+    (0x09, Key::Tab),        // VK_TAB
+    (0x0D, Key::Enter),      // VK_RETURN
+    (0x1B, Key::Escape),     // VK_ESCAPE
+    (0x20, Key::Space),      // VK_SPACE
+    (0x21, Key::PageUp),     // VK_PRIOR
+    (0x22, Key::PageDown),   // VK_NEXT
+    (0x23, Key::End),        // VK_END
+    (0x24, Key::Home),       // VK_HOME
+    (0x25, Key::ArrowLeft),  // VK_LEFT
+    (0x26, Key::ArrowUp),    // VK_UP
+    (0x27, Key::ArrowRight), // VK_RIGHT
+    (0x28, Key::ArrowDown),  // VK_DOWN
+    (0x2D, Key::Insert),     // VK_INSERT
+    (0x2E, Key::Delete),     // VK_DELETE
+    (0x30, Key::Num0),
+    (0x31, Key::Num1),
+    (0x32, Key::Num2),
+    (0x33, Key::Num3),
+    (0x34, Key::Num4),
+    (0x35, Key::Num5),
+    (0x36, Key::Num6),
+    (0x37, Key::Num7),
+    (0x38, Key::Num8),
+    (0x39, Key::Num9),
+    (0x41, Key::A),
+    (0x42, Key::B),
+    (0x43, Key::C),
+    (0x44, Key::D),
+    (0x45, Key::E),
+    (0x46, Key::F),
+    (0x47, Key::G),
+    (0x48, Key::H),
+    (0x49, Key::I),
+    (0x4A, Key::J),
+    (0x4B, Key::K),
+    (0x4C, Key::L),
+    (0x4D, Key::M),
+    (0x4E, Key::N),
+    (0x4F, Key::O),
+    (0x50, Key::P),
+    (0x51, Key::Q),
+    (0x52, Key::R),
+    (0x53, Key::S),
+    (0x54, Key::T),
+    (0x55, Key::U),
+    (0x56, Key::V),
+    (0x57, Key::W),
+    (0x58, Key::X),
+    (0x59, Key::Y),
+    (0x5A, Key::Z),
+    // numpad, egui doesn't tell those apart from the regular keys
+    (0x60, Key::Num0), // VK_NUMPAD0
+    (0x61, Key::Num1),
+    (0x62, Key::Num2),
+    (0x63, Key::Num3),
+    (0x64, Key::Num4),
+    (0x65, Key::Num5),
+    (0x66, Key::Num6),
+    (0x67, Key::Num7),
+    (0x68, Key::Num8),
+    (0x69, Key::Num9),
+    (0x6B, Key::Plus),   // VK_ADD
+    (0x6D, Key::Minus),  // VK_SUBTRACT
+    (0x6E, Key::Period), // VK_DECIMAL
+    (0x6F, Key::Slash),  // VK_DIVIDE
+    (0x70, Key::F1),
+    (0x71, Key::F2),
+    (0x72, Key::F3),
+    (0x73, Key::F4),
+    (0x74, Key::F5),
+    (0x75, Key::F6),
+    (0x76, Key::F7),
+    (0x77, Key::F8),
+    (0x78, Key::F9),
+    (0x79, Key::F10),
+    (0x7A, Key::F11),
+    (0x7B, Key::F12),
+    (0x7C, Key::F13),
+    (0x7D, Key::F14),
+    (0x7E, Key::F15),
+    (0x7F, Key::F16),
+    (0x80, Key::F17),
+    (0x81, Key::F18),
+    (0x82, Key::F19),
+    (0x83, Key::F20),
+    (0x84, Key::F21),
+    (0x85, Key::F22),
+    (0x86, Key::F23),
+    (0x87, Key::F24),
+    // the oem keys, as they are on a us layout.
+    // only used if the current layout doesn't type anything on them.
+    (0xBA, Key::Semicolon),    // VK_OEM_1
+    (0xBB, Key::Equals),       // VK_OEM_PLUS
+    (0xBC, Key::Comma),        // VK_OEM_COMMA
+    (0xBD, Key::Minus),        // VK_OEM_MINUS
+    (0xBE, Key::Period),       // VK_OEM_PERIOD
+    (0xBF, Key::Slash),        // VK_OEM_2
+    (0xC0, Key::Backtick),     // VK_OEM_3
+    (0xDB, Key::OpenBracket),  // VK_OEM_4
+    (0xDC, Key::Backslash),    // VK_OEM_5
+    (0xDD, Key::CloseBracket), // VK_OEM_6
+    (0xDE, Key::Quote),        // VK_OEM_7
+    (0xE2, Key::Backslash),    // VK_OEM_102
+];
+
+// set 1 scan codes, as they sit on a us keyboard.
+const SCANCODES: &[(u8, Key)] = &[
+    (0x01, Key::Escape),
+    (0x02, Key::Num1),
+    (0x03, Key::Num2),
+    (0x04, Key::Num3),
+    (0x05, Key::Num4),
+    (0x06, Key::Num5),
+    (0x07, Key::Num6),
+    (0x08, Key::Num7),
+    (0x09, Key::Num8),
+    (0x0A, Key::Num9),
+    (0x0B, Key::Num0),
+    (0x0C, Key::Minus),
+    (0x0D, Key::Equals),
+    (0x0E, Key::Backspace),
+    (0x0F, Key::Tab),
+    (0x10, Key::Q),
+    (0x11, Key::W),
+    (0x12, Key::E),
+    (0x13, Key::R),
+    (0x14, Key::T),
+    (0x15, Key::Y),
+    (0x16, Key::U),
+    (0x17, Key::I),
+    (0x18, Key::O),
+    (0x19, Key::P),
+    (0x1A, Key::OpenBracket),
+    (0x1B, Key::CloseBracket),
+    (0x1C, Key::Enter),
+    (0x1E, Key::A),
+    (0x1F, Key::S),
+    (0x20, Key::D),
+    (0x21, Key::F),
+    (0x22, Key::G),
+    (0x23, Key::H),
+    (0x24, Key::J),
+    (0x25, Key::K),
+    (0x26, Key::L),
+    (0x27, Key::Semicolon),
+    (0x28, Key::Quote),
+    (0x29, Key::Backtick),
+    (0x2B, Key::Backslash),
+    (0x2C, Key::Z),
+    (0x2D, Key::X),
+    (0x2E, Key::C),
+    (0x2F, Key::V),
+    (0x30, Key::B),
+    (0x31, Key::N),
+    (0x32, Key::M),
+    (0x33, Key::Comma),
+    (0x34, Key::Period),
+    (0x35, Key::Slash),
+    (0x39, Key::Space),
+    (0x3B, Key::F1),
+    (0x3C, Key::F2),
+    (0x3D, Key::F3),
+    (0x3E, Key::F4),
+    (0x3F, Key::F5),
+    (0x40, Key::F6),
+    (0x41, Key::F7),
+    (0x42, Key::F8),
+    (0x43, Key::F9),
+    (0x44, Key::F10),
+    // numpad
+    (0x47, Key::Num7),
+    (0x48, Key::Num8),
+    (0x49, Key::Num9),
+    (0x4A, Key::Minus),
+    (0x4B, Key::Num4),
+    (0x4C, Key::Num5),
+    (0x4D, Key::Num6),
+    (0x4E, Key::Plus),
+    (0x4F, Key::Num1),
+    (0x50, Key::Num2),
+    (0x51, Key::Num3),
+    (0x52, Key::Num0),
+    (0x53, Key::Period),
+    (0x56, Key::Backslash), // the extra key next to left shift on iso keyboards
+    (0x57, Key::F11),
+    (0x58, Key::F12),
+    (0x64, Key::F13),
+    (0x65, Key::F14),
+    (0x66, Key::F15),
+    (0x67, Key::F16),
+    (0x68, Key::F17),
+    (0x69, Key::F18),
+    (0x6A, Key::F19),
+    (0x6B, Key::F20),
+    (0x6C, Key::F21),
+    (0x6D, Key::F22),
+    (0x6E, Key::F23),
+    (0x76, Key::F24),
+];
+
+// scan codes with the extended bit set, these share their code with a numpad key.
+const EXTENDED_SCANCODES: &[(u8, Key)] = &[
+    (0x1C, Key::Enter), // numpad enter
+    (0x35, Key::Slash), // numpad divide
+    (0x47, Key::Home),
+    (0x48, Key::ArrowUp),
+    (0x49, Key::PageUp),
+    (0x4B, Key::ArrowLeft),
+    (0x4D, Key::ArrowRight),
+    (0x4F, Key::End),
+    (0x50, Key::ArrowDown),
+    (0x51, Key::PageDown),
+    (0x52, Key::Insert),
+    (0x53, Key::Delete),
+];
+
+/// the `VK_OEM_*` keys, which type something different on every layout.
+const fn is_oem_key(vk: u16) -> bool {
+    matches!(vk, 0xBA..=0xC0 | 0xDB..=0xDF | 0xE2)
+}
+
+/// the logical key egui should see for a virtual key.
+///
+/// `layout_char` is what the key types on the current layout, without modifiers.
+/// we go by that for the `VK_OEM_*` keys, since those move around between layouts.
+/// if it types something egui has no key for, e.g. a german '#', there is no logical key,
+/// guessing the us one would only trigger the wrong shortcuts.
+pub fn key_from_vk(vk: u16, layout_char: Option<char>) -> Option<Key> {
+    if is_oem_key(vk) {
+        if let Some(ch) = layout_char {
+            return key_from_char(ch);
+        }
+    }
+
+    VIRTUAL_KEYS
+        .iter()
+        .find(|(code, _)| *code == vk)
+        .map(|(_, key)| *key)
+}
+
+/// the key typing `ch` corresponds to, for the punctuation egui knows about.
+pub const fn key_from_char(ch: char) -> Option<Key> {
+    Some(match ch {
+        ':' => Key::Colon,
+        ',' => Key::Comma,
+        '\\' => Key::Backslash,
+        '/' => Key::Slash,
+        '|' => Key::Pipe,
+        '?' => Key::Questionmark,
+        '[' => Key::OpenBracket,
+        ']' => Key::CloseBracket,
+        '`' => Key::Backtick,
+        '-' => Key::Minus,
+        '.' => Key::Period,
+        '+' => Key::Plus,
+        '=' => Key::Equals,
+        ';' => Key::Semicolon,
+        '\'' => Key::Quote,
+        _ => return None,
+    })
+}
+
+/// the key at this position on a us keyboard, no matter the layout.
+pub fn physical_key(scancode: u8, extended: bool) -> Option<Key> {
+    let table = if extended {
+        EXTENDED_SCANCODES
+    } else {
+        SCANCODES
+    };
+
+    table
+        .iter()
+        .find(|(code, _)| *code == scancode)
+        .map(|(_, key)| *key)
+}
+
+/// the scan code and extended bit of a key message.
+pub const fn scancode_from_lparam(lparam: isize) -> (u8, bool) {
+    let scancode = ((lparam >> 16) & 0xFF) as u8;
+    let extended = lparam & (1 << 24) != 0;
+
+    (scancode, extended)
+}
+
+/// which modifier keys are held, tracked from the key messages themselves.
+///
+/// the windows keys are tracked too, egui just has nothing to report them with.
+///
+/// altgr arrives as a left ctrl followed by a right alt, with the same message time.
+/// that ctrl isn't really held, so it doesn't count towards [`ModifierState::ctrl`] while altgr is down.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ModifierState {
+    pub left_shift: bool,
+    pub right_shift: bool,
+    pub left_ctrl: bool,
+    pub right_ctrl: bool,
+    pub left_alt: bool,
+    pub right_alt: bool,
+    pub left_win: bool,
+    pub right_win: bool,
+    // the left ctrl is the one windows makes up for altgr
+    altgr: bool,
+    // message time of the last left ctrl down, while it's held
+    left_ctrl_time: Option<u32>,
+}
+
+impl ModifierState {
+    /// every modifier we track, as its left/right specific virtual key.
+    pub const VIRTUAL_KEYS: [u16; 8] = [
+        0xA0, // VK_LSHIFT
+        0xA1, // VK_RSHIFT
+        0xA2, // VK_LCONTROL
+        0xA3, // VK_RCONTROL
+        0xA4, // VK_LMENU
+        0xA5, // VK_RMENU
+        0x5B, // VK_LWIN
+        0x5C, // VK_RWIN
+    ];
+
+    /// feed a key message, `time` is from `GetMessageTime`. returns `false` if `vk` isn't a modifier.
+    ///
+    /// key messages only carry the generic `VK_SHIFT`/`VK_CONTROL`/`VK_MENU`,
+    /// the scan code or the extended bit tell us which side it was.
+    pub fn update(
+        &mut self,
+        vk: u16,
+        scancode: u8,
+        extended: bool,
+        pressed: bool,
+        time: u32,
+    ) -> bool {
+        let vk = match vk {
+            // VK_SHIFT, right shift has its own scan code
+            0x10 if scancode == 0x36 => 0xA1,
+            0x10 => 0xA0,
+            // VK_CONTROL, VK_MENU: the right one is extended
+            0x11 if extended => 0xA3,
+            0x11 => 0xA2,
+            0x12 if extended => 0xA5,
+            0x12 => 0xA4,
+            vk => vk,
+        };
+
+        match (vk, pressed) {
+            (0xA2, true) => self.left_ctrl_time = Some(time),
+            (0xA2, false) => self.left_ctrl_time = None,
+            // altgr repeats both keys with the same time too, so this holds while it's down.
+            (0xA5, true) => self.altgr = self.left_ctrl && self.left_ctrl_time == Some(time),
+            (0xA5, false) => self.altgr = false,
+            _ => {}
+        }
+
+        match self.key_mut(vk) {
+            Some(held) => {
+                *held = pressed;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// start over from what the os says is held, e.g. after we got focus back.
+    pub fn resync(&mut self, is_down: impl Fn(u16) -> bool) {
+        for vk in Self::VIRTUAL_KEYS {
+            if let Some(held) = self.key_mut(vk) {
+                *held = is_down(vk);
+            }
+        }
+
+        // the os can't tell us whether that ctrl was made up. both at once are most likely altgr.
+        self.altgr = self.left_ctrl && self.right_alt;
+        self.left_ctrl_time = None;
+    }
+
+    pub const fn shift(&self) -> bool {
+        self.left_shift || self.right_shift
+    }
+
+    pub const fn ctrl(&self) -> bool {
+        (self.left_ctrl && !self.altgr) || self.right_ctrl
+    }
+
+    pub const fn alt(&self) -> bool {
+        self.left_alt || self.right_alt
+    }
+
+    pub const fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            alt: self.alt(),
+            ctrl: self.ctrl(),
+            shift: self.shift(),
+            mac_cmd: false,
+            command: self.ctrl(),
+        }
+    }
+
+    fn key_mut(&mut self, vk: u16) -> Option<&mut bool> {
+        Some(match vk {
+            0xA0 => &mut self.left_shift,
+            0xA1 => &mut self.right_shift,
+            0xA2 => &mut self.left_ctrl,
+            0xA3 => &mut self.right_ctrl,
+            0xA4 => &mut self.left_alt,
+            0xA5 => &mut self.right_alt,
+            0x5B => &mut self.left_win,
+            0x5C => &mut self.right_win,
+            _ => return None,
+        })
+    }
+}
+
+/// puts `WM_CHAR`s back together, anything outside the bmp arrives as two utf-16 surrogates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Utf16Decoder {
+    high_surrogate: Option<u16>,
+}
+
+impl Utf16Decoder {
+    /// feed one utf-16 unit, returns a char once there is a complete one.
+    ///
+    /// a surrogate without its other half is dropped, like `String::from_utf16` would refuse it.
+    pub fn push(&mut self, unit: u16) -> Option<char> {
+        match unit {
+            0xD800..=0xDBFF => {
+                self.high_surrogate = Some(unit);
+                None
+            }
+            0xDC00..=0xDFFF => {
+                let high = self.high_surrogate.take()?;
+                let ch = 0x10000 + ((u32::from(high) - 0xD800) << 10) + (u32::from(unit) - 0xDC00);
+
+                char::from_u32(ch)
+            }
+            _ => {
+                self.high_surrogate = None;
+                char::from_u32(unit.into())
+            }
+        }
+    }
+
+    /// forget a half finished pair, e.g. after we lost focus.
+    pub fn reset(&mut self) {
+        self.high_surrogate = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a key message's lparam, with a repeat count of one.
+    const fn lparam(scancode: u8, extended: bool) -> isize {
+        1 | (scancode as isize) << 16 | (extended as isize) << 24
+    }
+
+    #[test]
+    fn numpad_and_navigation_keys() {
+        // the numpad with num lock on, and the keys sharing their scan codes.
+        let cases = [
+            (0x47, Key::Num7, Key::Home),
+            (0x48, Key::Num8, Key::ArrowUp),
+            (0x49, Key::Num9, Key::PageUp),
+            (0x4B, Key::Num4, Key::ArrowLeft),
+            (0x4D, Key::Num6, Key::ArrowRight),
+            (0x4F, Key::Num1, Key::End),
+            (0x50, Key::Num2, Key::ArrowDown),
+            (0x51, Key::Num3, Key::PageDown),
+            (0x52, Key::Num0, Key::Insert),
+            (0x53, Key::Period, Key::Delete),
+            (0x1C, Key::Enter, Key::Enter),
+            (0x35, Key::Slash, Key::Slash),
+        ];
+
+        for (scancode, numpad, extended) in cases {
+            assert_eq!(physical_key(scancode, false), Some(numpad), "{scancode:#x}");
+            assert_eq!(
+                physical_key(scancode, true),
+                Some(extended),
+                "{scancode:#x}"
+            );
+        }
+
+        let virtual_keys = [
+            (0x60, Key::Num0), // VK_NUMPAD0
+            (0x69, Key::Num9), // VK_NUMPAD9
+            (0x6B, Key::Plus), // VK_ADD
+            (0x6D, Key::Minus),
+            (0x6E, Key::Period),
+            (0x6F, Key::Slash),
+            (0x24, Key::Home), // VK_HOME
+            (0x2D, Key::Insert),
+            (0x2E, Key::Delete),
+            (0x21, Key::PageUp),
+            (0x28, Key::ArrowDown),
+        ];
+
+        for (vk, key) in virtual_keys {
+            assert_eq!(key_from_vk(vk, None), Some(key), "{vk:#x}");
+        }
+    }
+
+    #[test]
+    fn extended_bit() {
+        let cases = [
+            (lparam(0x47, false), (0x47, false)),
+            (lparam(0x47, true), (0x47, true)),
+            (lparam(0x1D, true), (0x1D, true)),
+            // the previous state and transition bits don't matter.
+            (lparam(0x2A, false) | 3 << 30, (0x2A, false)),
+            (lparam(0x38, true) | 1 << 29, (0x38, true)),
+        ];
+
+        for (lparam, expected) in cases {
+            assert_eq!(scancode_from_lparam(lparam), expected, "{lparam:#x}");
+        }
+    }
+
+    /// which sides are held, in the order of [`ModifierState::VIRTUAL_KEYS`].
+    fn sides(state: &ModifierState) -> [bool; 8] {
+        [
+            state.left_shift,
+            state.right_shift,
+            state.left_ctrl,
+            state.right_ctrl,
+            state.left_alt,
+            state.right_alt,
+            state.left_win,
+            state.right_win,
+        ]
+    }
+
+    #[test]
+    fn left_and_right_modifiers() {
+        // vk, scan code, extended, the side that ends up held
+        let cases = [
+            (0x10, 0x2A, false, 0xA0), // VK_LSHIFT
+            (0x10, 0x36, false, 0xA1),
+            (0x11, 0x1D, false, 0xA2), // VK_LCONTROL
+            (0x11, 0x1D, true, 0xA3),
+            (0x12, 0x38, false, 0xA4), // VK_LMENU
+            (0x12, 0x38, true, 0xA5),
+            (0x5B, 0x5B, true, 0x5B), // VK_LWIN
+            (0x5C, 0x5C, true, 0x5C),
+        ];
+
+        for (vk, scancode, extended, side) in cases {
+            let mut state = ModifierState::default();
+            assert!(state.update(vk, scancode, extended, true, 0), "{vk:#x}");
+
+            // exactly that side is down.
+            let expected = ModifierState::VIRTUAL_KEYS.map(|vk| vk == side);
+            assert_eq!(sides(&state), expected, "{vk:#x} {scancode:#x} {extended}");
+
+            assert!(state.update(vk, scancode, extended, false, 0));
+            assert_eq!(state, ModifierState::default());
+        }
+
+        let mut state = ModifierState::default();
+        state.update(0x10, 0x2A, false, true, 0);
+        state.update(0x10, 0x36, false, true, 0);
+        state.update(0x10, 0x2A, false, false, 0);
+        // still held on the other side.
+        assert!(state.shift());
+        assert!(state.modifiers().shift);
+
+        state.update(0x11, 0x1D, true, true, 0);
+        assert!(state.modifiers().ctrl && state.modifiers().command);
+
+        assert!(!state.update(0x41, 0x1E, false, true, 0));
+    }
+
+    #[test]
+    fn altgr_is_not_ctrl() {
+        let mut state = ModifierState::default();
+
+        // what windows sends for altgr, both with the same time.
+        state.update(0x11, 0x1D, false, true, 100);
+        state.update(0x12, 0x38, true, true, 100);
+        assert!(!state.ctrl());
+        assert!(!state.modifiers().command);
+        assert!(state.alt());
+
+        // holding it repeats both.
+        state.update(0x11, 0x1D, false, true, 130);
+        state.update(0x12, 0x38, true, true, 130);
+        assert!(!state.ctrl());
+
+        // a real right ctrl still counts.
+        state.update(0x11, 0x1D, true, true, 140);
+        assert!(state.ctrl());
+        state.update(0x11, 0x1D, true, false, 150);
+
+        state.update(0x12, 0x38, true, false, 200);
+        state.update(0x11, 0x1D, false, false, 200);
+        assert_eq!(state, ModifierState::default());
+
+        // a real left ctrl, then right alt later on.
+        state.update(0x11, 0x1D, false, true, 300);
+        state.update(0x12, 0x38, true, true, 450);
+        assert!(state.ctrl() && state.alt());
+    }
+
+    #[test]
+    fn resync_from_the_os() {
+        let mut state = ModifierState {
+            left_shift: true,
+            ..ModifierState::default()
+        };
+
+        state.resync(|vk| vk == 0xA5);
+        assert_eq!(
+            state,
+            ModifierState {
+                right_alt: true,
+                ..ModifierState::default()
+            }
+        );
+
+        // left ctrl and right alt, as the os reports altgr.
+        state.resync(|vk| vk == 0xA2 || vk == 0xA5);
+        assert!(!state.ctrl() && state.alt());
+    }
+
+    #[test]
+    fn oem_keys_follow_the_layout() {
+        // vk, what the layout types, what egui should see
+        let cases = [
+            // us
+            (0xBB, Some('='), Some(Key::Equals)),
+            (0xBD, Some('-'), Some(Key::Minus)),
+            (0xBA, Some(';'), Some(Key::Semicolon)),
+            (0xDE, Some('\''), Some(Key::Quote)),
+            (0xC0, Some('`'), Some(Key::Backtick)),
+            // german: '+' right of the ü, '#' left of enter. egui has no key for '#' or 'ö'.
+            (0xBB, Some('+'), Some(Key::Plus)),
+            (0xBF, Some('#'), None),
+            (0xC0, Some('ö'), None),
+            // nothing from the layout, use where it is on a us keyboard.
+            (0xBB, None, Some(Key::Equals)),
+            (0xDB, None, Some(Key::OpenBracket)),
+            (0xE2, None, Some(Key::Backslash)),
+            // VK_OEM_8 isn't on a us keyboard, there's nothing to fall back to.
+            (0xDF, Some('!'), None),
+            (0xDF, None, None),
+        ];
+
+        for (vk, layout_char, key) in cases {
+            assert_eq!(key_from_vk(vk, layout_char), key, "{vk:#x} {layout_char:?}");
+        }
+
+        // only the oem keys go by the layout, letters stay where they are.
+        assert_eq!(key_from_vk(0x5A, Some('y')), Some(Key::Z));
+    }
+
+    fn decode(units: &[u16]) -> Vec<char> {
+        let mut decoder = Utf16Decoder::default();
+        units
+            .iter()
+            .filter_map(|&unit| decoder.push(unit))
+            .collect()
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        let text = "a€😀𝄞";
+        let units: Vec<u16> = text.encode_utf16().collect();
+        assert_eq!(units.len(), 6);
+
+        assert_eq!(decode(&units), text.chars().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn lone_surrogates() {
+        // a low surrogate on its own.
+        assert_eq!(decode(&[0xDE00, 0x61]), ['a']);
+        // a high surrogate followed by something that isn't its low half.
+        assert_eq!(decode(&[0xD83D, 0x61]), ['a']);
+        // the second high surrogate wins.
+        assert_eq!(decode(&[0xD800, 0xD83D, 0xDE00]), ['😀']);
+        // the pair is used up, the next low surrogate has nothing to go with.
+        assert_eq!(decode(&[0xD83D, 0xDE00, 0xDE00]), ['😀']);
+    }
+
+    #[test]
+    fn reset_drops_half_a_pair() {
+        let mut decoder = Utf16Decoder::default();
+        assert_eq!(decoder.push(0xD83D), None);
+
+        decoder.reset();
+        assert_eq!(decoder.push(0xDE00), None);
+        assert_eq!(decoder.push(0x61), Some('a'));
+    }
+}
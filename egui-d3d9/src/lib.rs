@@ -5,34 +5,68 @@
     clippy::cast_precision_loss
 )]
 
-
+// everything talking to windows itself is only built there,
+// the renderer core builds (and runs, with a `RecordingDevice`) anywhere.
+#[cfg(windows)]
 mod app;
+#[cfg(windows)]
+mod builder;
+mod clipboard;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod cursor;
+#[cfg(windows)]
+mod d3d9;
+mod device;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod dropfiles;
+mod error;
+mod golden;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod ime;
+#[cfg(windows)]
 mod inputman;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod keys;
 mod mesh;
+#[cfg(windows)]
+mod platform;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod rawinput;
+mod recording;
+mod renderer;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod repaint;
+#[cfg(windows)]
+mod sink;
+mod software;
+#[cfg(windows)]
 mod state;
 mod texman;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod touch;
+#[cfg_attr(not(windows), allow(dead_code))]
+mod wheel;
 
-use std::sync::Mutex;
-
+#[cfg(windows)]
 pub use app::*;
-use clipboard::ClipboardProvider;
-
-static CLIPBOARD: Mutex<Option<clipboard::ClipboardContext>> = Mutex::new(None);
-
-pub(crate) fn get_clipboard_text() -> Result<String, Box<dyn std::error::Error>> {
-    let mut ctx = CLIPBOARD.lock().unwrap();
-    if ctx.is_none() {
-        *ctx = Some(clipboard::ClipboardContext::new()?);
-    }
-
-    ctx.as_mut().unwrap().get_contents()
-}
-
-pub(crate) fn set_clipboard_text(s: String) -> Result<(), Box<dyn std::error::Error>> {
-    let mut ctx = CLIPBOARD.lock().unwrap();
-    if ctx.is_none() {
-        *ctx = Some(clipboard::ClipboardContext::new()?);
-    }
-
-    ctx.as_mut().unwrap().set_contents(s)
-}
+#[cfg(windows)]
+pub use builder::*;
+#[cfg(windows)]
+pub use clipboard::NativeClipboard;
+pub use clipboard::{ClipboardProvider, MemoryClipboard};
+pub use device::{ClipRect, RenderDevice};
+pub use error::{Error, PlatformError, Result};
+pub use golden::{
+    compare_golden, diff_images, read_image, write_image, GoldenError, ImageDiff, BLESS_ENV,
+};
+pub use mesh::{GpuVertex, VertexColor};
+pub use recording::{Command, RecordingDevice};
+pub use renderer::Renderer;
+#[cfg(windows)]
+pub use sink::{InputBlocking, InputSink, WndProcResult};
+pub use software::{SoftwareDevice, SoftwareTexture};
+#[cfg(windows)]
+pub use state::RenderTarget;
+pub use texman::TextureColor;
+#[cfg(windows)]
+pub use wheel::WheelConfig;
@@ -1,20 +1,9 @@
-use egui::{Color32, Mesh, Pos2, Rect, TextureId};
-use windows::Win32::{
-    Foundation::{HANDLE, RECT},
-    Graphics::Direct3D9::{
-        IDirect3DDevice9, IDirect3DIndexBuffer9, IDirect3DVertexBuffer9, D3DFMT_INDEX32,
-        D3DFVF_DIFFUSE, D3DFVF_TEX1, D3DFVF_XYZ, D3DLOCK_DISCARD, D3DPOOL_DEFAULT,
-        D3DUSAGE_DYNAMIC, D3DUSAGE_WRITEONLY,
-    },
-};
+use egui::{epaint::PaintCallback, Color32, Mesh, Pos2, Rect, TextureId};
 
-// XYZ is 32 bits completely wasted per vertex.
-// but that's the cost of doing business, I really cba dealing with shaders again
-// although I'll probably do it at some point
-pub const FVF_CUSTOMVERTEX: u32 = D3DFVF_XYZ | D3DFVF_DIFFUSE | D3DFVF_TEX1;
+use crate::{ClipRect, Error, RenderDevice, Result};
 
 #[repr(C)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct VertexColor {
     pub b: u8,
     pub g: u8,
@@ -34,23 +23,42 @@ impl From<Color32> for VertexColor {
     }
 }
 
+/// A single draw step, in the order egui emitted it.
+pub enum PrimitiveDescriptor {
+    Mesh(MeshDescriptor),
+    Callback(CallbackDescriptor),
+}
+
+pub struct CallbackDescriptor {
+    pub callback: PaintCallback,
+    pub clip: Rect,
+    pub pixels_per_point: f32,
+}
+
 pub struct MeshDescriptor {
     pub vertices: usize,
     pub indices: usize,
-    pub clip: RECT,
+    pub clip: ClipRect,
     pub texture_id: TextureId,
 }
 
 impl MeshDescriptor {
-    pub fn from_mesh(mesh: Mesh, scissors: Rect) -> Option<(Self, Vec<GpuVertex>, Vec<u32>)> {
-        if mesh.indices.is_empty() || mesh.indices.len() % 3 != 0 {
+    /// egui hands us points, the device wants pixels.
+    /// `screen_size` is in pixels and only used to keep the scissor rect on screen.
+    pub fn from_mesh(
+        mesh: Mesh,
+        scissors: Rect,
+        pixels_per_point: f32,
+        screen_size: [u32; 2],
+    ) -> Option<(Self, Vec<GpuVertex>, Vec<u32>)> {
+        if mesh.indices.is_empty() || !mesh.indices.len().is_multiple_of(3) {
             return None;
         }
         let vertices: Vec<GpuVertex> = mesh
             .vertices
             .into_iter()
             .map(|v| GpuVertex {
-                pos: [v.pos.x, v.pos.y, 0f32],
+                pos: [v.pos.x * pixels_per_point, v.pos.y * pixels_per_point, 0f32],
                 uv: v.uv,
                 color: v.color.into(),
             })
@@ -60,12 +68,7 @@ impl MeshDescriptor {
             Self {
                 vertices: vertices.len(),
                 indices: mesh.indices.len(),
-                clip: RECT {
-                    left: scissors.left() as _,
-                    top: scissors.top() as _,
-                    right: scissors.right() as _,
-                    bottom: scissors.bottom() as _,
-                },
+                clip: clip_rect_in_pixels(scissors, pixels_per_point, screen_size),
                 texture_id: mesh.texture_id,
             },
             vertices,
@@ -74,32 +77,44 @@ impl MeshDescriptor {
     }
 }
 
+fn clip_rect_in_pixels(clip: Rect, pixels_per_point: f32, screen_size: [u32; 2]) -> ClipRect {
+    // round outwards to whole pixels, same as the other egui backends.
+    let width = screen_size[0] as f32;
+    let height = screen_size[1] as f32;
+
+    ClipRect {
+        left: (clip.left() * pixels_per_point).floor().clamp(0., width) as _,
+        top: (clip.top() * pixels_per_point).floor().clamp(0., height) as _,
+        right: (clip.right() * pixels_per_point).ceil().clamp(0., width) as _,
+        bottom: (clip.bottom() * pixels_per_point).ceil().clamp(0., height) as _,
+    }
+}
+
+// XYZ is 32 bits completely wasted per vertex.
+// but that's the cost of doing business, I really cba dealing with shaders again
+// although I'll probably do it at some point
 #[repr(C)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct GpuVertex {
-    pos: [f32; 3],
-    color: VertexColor,
-    uv: Pos2,
+    pub pos: [f32; 3],
+    pub color: VertexColor,
+    pub uv: Pos2,
 }
 
-pub struct Buffers {
-    pub vtx: Option<IDirect3DVertexBuffer9>,
-    pub idx: Option<IDirect3DIndexBuffer9>,
+pub struct Buffers<D: RenderDevice> {
+    pub vtx: Option<D::VertexBuffer>,
+    pub idx: Option<D::IndexBuffer>,
     vtx_size: usize,
     idx_size: usize,
 }
 
-impl Buffers {
-    pub fn create_buffers(
-        device: &IDirect3DDevice9,
-        vtx_count: usize,
-        idx_count: usize,
-    ) -> windows::core::Result<Self> {
+impl<D: RenderDevice> Buffers<D> {
+    pub fn create_buffers(device: &D, vtx_count: usize, idx_count: usize) -> Result<Self> {
         Ok(Self {
             vtx_size: vtx_count,
             idx_size: idx_count,
-            vtx: Some(Self::create_vertex_buffer(device, vtx_count)?),
-            idx: Some(Self::create_index_buffer(device, idx_count)?),
+            vtx: Some(device.create_vertex_buffer(vtx_count)?),
+            idx: Some(device.create_index_buffer(idx_count)?),
         })
     }
 
@@ -108,110 +123,31 @@ impl Buffers {
         self.idx = None;
     }
 
-    fn create_vertex_buffer(
-        device: &IDirect3DDevice9,
-        vertices: usize,
-    ) -> windows::core::Result<IDirect3DVertexBuffer9> {
-        unsafe {
-            let mut vertex_buffer: Option<IDirect3DVertexBuffer9> = None;
-
-            device.CreateVertexBuffer(
-                (vertices * std::mem::size_of::<GpuVertex>()) as u32,
-                (D3DUSAGE_DYNAMIC | D3DUSAGE_WRITEONLY) as _,
-                FVF_CUSTOMVERTEX,
-                D3DPOOL_DEFAULT,
-                &mut vertex_buffer,
-                std::ptr::null_mut::<HANDLE>(),
-            )?;
-
-            Ok(vertex_buffer.expect("unable to create vertex buffer"))
-        }
-    }
+    pub fn update_vertex_buffer(&mut self, device: &D, vertices: &[GpuVertex]) -> Result<()> {
+        let buf_len = vertices.len();
 
-    fn create_index_buffer(
-        device: &IDirect3DDevice9,
-        indices: usize,
-    ) -> windows::core::Result<IDirect3DIndexBuffer9> {
-        let mut index_buffer: Option<IDirect3DIndexBuffer9> = None;
-        unsafe {
-            device.CreateIndexBuffer(
-                (indices * std::mem::size_of::<u32>()) as u32,
-                (D3DUSAGE_DYNAMIC | D3DUSAGE_WRITEONLY) as _,
-                D3DFMT_INDEX32,
-                D3DPOOL_DEFAULT,
-                &mut index_buffer,
-                std::ptr::null_mut::<HANDLE>(),
-            )?;
+        if self.vtx_size < buf_len {
+            let new_size = buf_len + 1024;
+            self.vtx = Some(device.create_vertex_buffer(new_size)?);
+            self.vtx_size = new_size;
         }
 
-        Ok(index_buffer.expect("unable to create index buffer"))
-    }
-
-    pub fn update_vertex_buffer(
-        &mut self,
-        device: &IDirect3DDevice9,
-        vertices: &[GpuVertex],
-    ) -> windows::core::Result<()> {
-        unsafe {
-            let buf_len = vertices.len();
-
-            if self.vtx_size < buf_len {
-                let new_size = buf_len + 1024;
-                self.vtx = Some(Self::create_vertex_buffer(device, new_size)?);
-                self.vtx_size = new_size;
-            }
-
-            let vtx = self.vtx.as_mut().expect("unable to get vertex buffer");
+        let vtx = self.vtx.as_ref().ok_or(Error::DeviceLost)?;
 
-            let mut buffer: *mut GpuVertex = std::mem::zeroed();
-
-            vtx.Lock(
-                0,
-                vertices.len() as u32 * std::mem::size_of::<GpuVertex>() as u32,
-                (&raw mut buffer).cast(),
-                D3DLOCK_DISCARD as _,
-            )?;
-            let buffer = std::slice::from_raw_parts_mut(buffer, vertices.len() as _);
-
-            buffer.copy_from_slice(vertices);
-
-            vtx.Unlock()?;
-            Ok(())
-        }
+        device.write_vertices(vtx, vertices)
     }
 
-    pub fn update_index_buffer(
-        &mut self,
-        device: &IDirect3DDevice9,
-        indices: &[u32],
-    ) -> windows::core::Result<()> {
-        unsafe {
-            let buf_len = indices.len();
+    pub fn update_index_buffer(&mut self, device: &D, indices: &[u32]) -> Result<()> {
+        let buf_len = indices.len();
 
-            if self.idx_size < buf_len {
-                let new_size = buf_len + 1024;
-                self.idx = Some(Self::create_index_buffer(device, new_size)?);
-                self.idx_size = new_size;
-            }
-
-            let idx = self.idx.as_mut().expect("unable to get index buffer");
-
-            let mut buffer: *mut u32 = std::mem::zeroed();
-
-            idx.Lock(
-                0,
-                indices.len() as u32 * std::mem::size_of::<u32>() as u32,
-                (&raw mut buffer).cast(),
-                D3DLOCK_DISCARD as _,
-            )?;
-
-            let buffer = std::slice::from_raw_parts_mut(buffer, indices.len() as _);
-
-            buffer.copy_from_slice(indices);
-
-            idx.Unlock()?;
+        if self.idx_size < buf_len {
+            let new_size = buf_len + 1024;
+            self.idx = Some(device.create_index_buffer(new_size)?);
+            self.idx_size = new_size;
         }
 
-        Ok(())
+        let idx = self.idx.as_ref().ok_or(Error::DeviceLost)?;
+
+        device.write_indices(idx, indices)
     }
 }
@@ -0,0 +1,235 @@
+use std::{ffi::OsString, os::windows::ffi::OsStringExt, path::PathBuf};
+
+use egui::{CursorIcon, DroppedFile, OpenUrl, Rect};
+use windows::{
+    core::{w, HSTRING, PCWSTR},
+    Win32::{
+        Foundation::{HWND, POINT, RECT},
+        UI::{
+            Input::Ime::{
+                ImmGetContext, ImmReleaseContext, ImmSetCandidateWindow, ImmSetCompositionWindow,
+                CANDIDATEFORM, CFS_EXCLUDE, CFS_POINT, COMPOSITIONFORM,
+            },
+            Input::KeyboardAndMouse::{GetCapture, ReleaseCapture, SetCapture},
+            Input::{
+                GetRegisteredRawInputDevices, RegisterRawInputDevices, RAWINPUTDEVICE,
+                RAWINPUTDEVICE_FLAGS, RIDEV_REMOVE,
+            },
+            Shell::{DragAcceptFiles, DragFinish, DragQueryFileW, ShellExecuteW, HDROP},
+            WindowsAndMessaging::{
+                LoadCursorW, SetCursor, IDC_APPSTARTING, IDC_ARROW, IDC_CROSS, IDC_HAND, IDC_HELP,
+                IDC_IBEAM, IDC_NO, IDC_SIZEALL, IDC_SIZENESW, IDC_SIZENS, IDC_SIZENWSE, IDC_SIZEWE,
+                IDC_WAIT, SW_SHOWNORMAL,
+            },
+        },
+    },
+};
+
+use crate::{
+    cursor::{self, SystemCursor},
+    dropfiles,
+};
+
+/// the `IDC_*` resource of a system cursor.
+const fn cursor_resource(cursor: SystemCursor) -> PCWSTR {
+    match cursor {
+        SystemCursor::Arrow => IDC_ARROW,
+        SystemCursor::Help => IDC_HELP,
+        SystemCursor::Hand => IDC_HAND,
+        SystemCursor::AppStarting => IDC_APPSTARTING,
+        SystemCursor::Wait => IDC_WAIT,
+        SystemCursor::Cross => IDC_CROSS,
+        SystemCursor::IBeam => IDC_IBEAM,
+        SystemCursor::No => IDC_NO,
+        SystemCursor::SizeAll => IDC_SIZEALL,
+        SystemCursor::SizeWe => IDC_SIZEWE,
+        SystemCursor::SizeNs => IDC_SIZENS,
+        SystemCursor::SizeNesw => IDC_SIZENESW,
+        SystemCursor::SizeNwse => IDC_SIZENWSE,
+    }
+}
+
+/// set the cursor for the current `WM_SETCURSOR`.
+pub fn apply_cursor(icon: CursorIcon) {
+    unsafe {
+        match cursor::system_cursor(icon) {
+            Some(cursor) => {
+                if let Ok(cursor) = LoadCursorW(None, cursor_resource(cursor)) {
+                    SetCursor(Some(cursor));
+                }
+            }
+            None => {
+                SetCursor(None);
+            }
+        }
+    }
+}
+
+pub fn open_url(url: &OpenUrl) {
+    if !cursor::is_web_url(&url.url) {
+        return;
+    }
+
+    unsafe {
+        ShellExecuteW(
+            None,
+            w!("open"),
+            &HSTRING::from(url.url.as_str()),
+            None,
+            None,
+            SW_SHOWNORMAL,
+        );
+    }
+}
+
+/// move the ime windows to the text cursor, `cursor` is in client pixels.
+///
+/// the composition goes right where the cursor is, the candidate list below it without covering it.
+pub fn set_ime_position(hwnd: HWND, cursor: Rect) {
+    let top_left = POINT {
+        x: cursor.min.x.round() as _,
+        y: cursor.min.y.round() as _,
+    };
+    let area = RECT {
+        left: cursor.min.x.floor() as _,
+        top: cursor.min.y.floor() as _,
+        right: cursor.max.x.ceil() as _,
+        bottom: cursor.max.y.ceil() as _,
+    };
+
+    unsafe {
+        let himc = ImmGetContext(hwnd);
+        if himc.is_invalid() {
+            return;
+        }
+
+        let composition = COMPOSITIONFORM {
+            dwStyle: CFS_POINT,
+            ptCurrentPos: top_left,
+            rcArea: area,
+        };
+        let _ = ImmSetCompositionWindow(himc, &composition);
+
+        let candidate = CANDIDATEFORM {
+            dwIndex: 0,
+            dwStyle: CFS_EXCLUDE,
+            ptCurrentPos: POINT {
+                x: area.left,
+                y: area.bottom,
+            },
+            rcArea: area,
+        };
+        let _ = ImmSetCandidateWindow(himc, &candidate);
+
+        let _ = ImmReleaseContext(hwnd, himc);
+    }
+}
+
+// generic desktop page, mouse usage
+const MOUSE_USAGE_PAGE: u16 = 0x01;
+const MOUSE_USAGE: u16 = 0x02;
+
+/// make sure `WM_INPUT` arrives for the mouse, `true` if we registered it ourselves.
+///
+/// most games that want raw input registered for it already, we leave that alone,
+/// since there can only be one registration per device type and we'd replace theirs.
+pub fn register_raw_mouse(hwnd: HWND) -> windows::core::Result<bool> {
+    let size = std::mem::size_of::<RAWINPUTDEVICE>() as u32;
+
+    unsafe {
+        let mut count = 0;
+        GetRegisteredRawInputDevices(None, &mut count, size);
+
+        let mut devices = vec![RAWINPUTDEVICE::default(); count as usize];
+        if count > 0
+            && GetRegisteredRawInputDevices(Some(devices.as_mut_ptr()), &mut count, size)
+                == u32::MAX
+        {
+            return Err(windows::core::Error::from_win32());
+        }
+
+        if devices
+            .iter()
+            .any(|device| device.usUsagePage == MOUSE_USAGE_PAGE && device.usUsage == MOUSE_USAGE)
+        {
+            return Ok(false);
+        }
+
+        RegisterRawInputDevices(
+            &[RAWINPUTDEVICE {
+                usUsagePage: MOUSE_USAGE_PAGE,
+                usUsage: MOUSE_USAGE,
+                dwFlags: RAWINPUTDEVICE_FLAGS(0),
+                hwndTarget: hwnd,
+            }],
+            size,
+        )?;
+    }
+
+    Ok(true)
+}
+
+/// undo [`register_raw_mouse`], only if it returned `true`, the game's registration isn't ours to remove.
+pub fn unregister_raw_mouse() -> windows::core::Result<()> {
+    unsafe {
+        RegisterRawInputDevices(
+            &[RAWINPUTDEVICE {
+                usUsagePage: MOUSE_USAGE_PAGE,
+                usUsage: MOUSE_USAGE,
+                dwFlags: RIDEV_REMOVE,
+                // has to be null for RIDEV_REMOVE.
+                hwndTarget: HWND::default(),
+            }],
+            std::mem::size_of::<RAWINPUTDEVICE>() as u32,
+        )
+    }
+}
+
+/// let the window take files dragged onto it, they arrive as `WM_DROPFILES`.
+pub fn accept_files(hwnd: HWND, accept: bool) {
+    unsafe { DragAcceptFiles(hwnd, accept) };
+}
+
+/// grab the mouse for `hwnd` or let go of it. either way, the window gets `WM_CAPTURECHANGED` before this returns.
+///
+/// letting go does nothing if another window took the mouse in the meantime.
+pub fn set_capture(hwnd: HWND, capture: bool) {
+    unsafe {
+        if capture {
+            SetCapture(hwnd);
+        } else if has_capture(hwnd) {
+            let _ = ReleaseCapture();
+        }
+    }
+}
+
+/// whether `hwnd` holds the mouse, in this thread.
+pub fn has_capture(hwnd: HWND) -> bool {
+    unsafe { GetCapture() == hwnd }
+}
+
+/// the files of a `WM_DROPFILES`, which is done with afterwards.
+pub fn take_dropped_files(hdrop: HDROP) -> Vec<DroppedFile> {
+    unsafe {
+        let count = DragQueryFileW(hdrop, u32::MAX, None);
+
+        let files = (0..count)
+            .filter_map(|i| {
+                // without the nul.
+                let len = DragQueryFileW(hdrop, i, None);
+                let mut path = vec![0u16; len as usize + 1];
+                let len = DragQueryFileW(hdrop, i, Some(&mut path));
+                if len == 0 {
+                    return None;
+                }
+
+                path.truncate(len as usize);
+                let path = PathBuf::from(OsString::from_wide(&path));
+                Some(dropfiles::dropped_file(path))
+            })
+            .collect();
+
+        DragFinish(hdrop);
+        files
+    }
+}
@@ -0,0 +1,144 @@
+use egui::{Color32, Context, Id, LayerId, Order, PointerButton, Pos2, Shape, Stroke, Vec2};
+
+/// `RI_MOUSE_*` button flags of a raw mouse event, and what they mean for egui.
+const BUTTONS: [(u16, PointerButton, bool); 10] = [
+    (0x0001, PointerButton::Primary, true),
+    (0x0002, PointerButton::Primary, false),
+    (0x0004, PointerButton::Secondary, true),
+    (0x0008, PointerButton::Secondary, false),
+    (0x0010, PointerButton::Middle, true),
+    (0x0020, PointerButton::Middle, false),
+    (0x0040, PointerButton::Extra1, true),
+    (0x0080, PointerButton::Extra1, false),
+    (0x0100, PointerButton::Extra2, true),
+    (0x0200, PointerButton::Extra2, false),
+];
+
+/// `RI_MOUSE_WHEEL`
+pub const WHEEL: u16 = 0x0400;
+/// `RI_MOUSE_HWHEEL`
+pub const HWHEEL: u16 = 0x0800;
+
+/// the button presses and releases in `usButtonFlags`, in order.
+pub fn buttons(flags: u16) -> impl Iterator<Item = (PointerButton, bool)> {
+    BUTTONS
+        .into_iter()
+        .filter(move |(flag, _, _)| flags & flag != 0)
+        .map(|(_, button, pressed)| (button, pressed))
+}
+
+/// a cursor we move ourselves, for games that keep the real one locked in place.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VirtualCursor {
+    // in pixels, `None` until the first move
+    pos: Option<Pos2>,
+}
+
+impl VirtualCursor {
+    /// move by a raw mouse delta and keep the cursor inside `screen_size`, both in pixels.
+    ///
+    /// starts out in the middle of the screen.
+    pub fn move_by(&mut self, delta: Vec2, screen_size: Vec2) -> Pos2 {
+        let pos = self.pos.unwrap_or((screen_size / 2.).to_pos2()) + delta;
+        let max = (screen_size - Vec2::splat(1.)).max(Vec2::ZERO).to_pos2();
+        let pos = pos.clamp(Pos2::ZERO, max);
+
+        self.pos = Some(pos);
+        pos
+    }
+
+    /// where the cursor is, in pixels.
+    pub const fn pos(&self) -> Option<Pos2> {
+        self.pos
+    }
+}
+
+/// stroke widths of the cursor, in points.
+const TAIL_OUTLINE: f32 = 4.;
+const TAIL: f32 = 2.;
+const OUTLINE: f32 = 1.;
+
+/// draw an arrow with its tip at `pos`, on top of everything else egui shows.
+pub fn paint_cursor(ctx: &Context, pos: Pos2) {
+    let painter = ctx.layer_painter(LayerId::new(Order::Debug, Id::new("egui_d3d9_cursor")));
+
+    let at = |x: f32, y: f32| pos + Vec2::new(x, y);
+
+    // the tail first, so the head covers where they meet.
+    painter.line_segment(
+        [at(4., 11.), at(7.5, 17.5)],
+        Stroke::new(TAIL_OUTLINE, Color32::BLACK),
+    );
+    painter.line_segment(
+        [at(4., 11.), at(7.5, 17.5)],
+        Stroke::new(TAIL, Color32::WHITE),
+    );
+    painter.add(Shape::convex_polygon(
+        vec![at(0., 0.), at(0., 15.), at(10.5, 10.5)],
+        Color32::WHITE,
+        Stroke::new(OUTLINE, Color32::BLACK),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Vec2 = Vec2::new(800., 600.);
+
+    #[test]
+    fn starts_in_the_middle() {
+        let mut cursor = VirtualCursor::default();
+        assert_eq!(cursor.pos(), None);
+
+        assert_eq!(cursor.move_by(Vec2::ZERO, SCREEN), Pos2::new(400., 300.));
+        assert_eq!(
+            cursor.move_by(Vec2::new(10., -20.), SCREEN),
+            Pos2::new(410., 280.)
+        );
+        assert_eq!(cursor.pos(), Some(Pos2::new(410., 280.)));
+    }
+
+    #[test]
+    fn stays_on_screen() {
+        let mut cursor = VirtualCursor::default();
+
+        assert_eq!(
+            cursor.move_by(Vec2::new(-5000., -5000.), SCREEN),
+            Pos2::ZERO
+        );
+        assert_eq!(
+            cursor.move_by(Vec2::new(5000., 5000.), SCREEN),
+            Pos2::new(799., 599.)
+        );
+        // moving back doesn't have to make up for what got clamped.
+        assert_eq!(
+            cursor.move_by(Vec2::new(-9., -9.), SCREEN),
+            Pos2::new(790., 590.)
+        );
+
+        // the window got smaller.
+        assert_eq!(
+            cursor.move_by(Vec2::ZERO, Vec2::new(640., 480.)),
+            Pos2::new(639., 479.)
+        );
+        // or has no size at all, e.g. while minimized.
+        assert_eq!(cursor.move_by(Vec2::new(3., 3.), Vec2::ZERO), Pos2::ZERO);
+    }
+
+    #[test]
+    fn button_flags() {
+        assert_eq!(buttons(0).count(), 0);
+        assert_eq!(buttons(WHEEL | HWHEEL).count(), 0);
+
+        // RI_MOUSE_LEFT_BUTTON_UP | RI_MOUSE_RIGHT_BUTTON_DOWN | RI_MOUSE_BUTTON_5_UP
+        assert_eq!(
+            buttons(0x0002 | 0x0004 | 0x0200).collect::<Vec<_>>(),
+            [
+                (PointerButton::Primary, false),
+                (PointerButton::Secondary, true),
+                (PointerButton::Extra2, false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,719 @@
+use std::cell::{Cell, RefCell};
+
+use egui::{epaint::PaintCallbackInfo, PaintCallback, Rect, TextureOptions};
+
+use crate::{ClipRect, Error, GpuVertex, RenderDevice, Result, TextureColor};
+
+/// one call the renderer made into a [`RecordingDevice`].
+///
+/// buffers and textures are identified by the id they were created with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    CreateVertexBuffer {
+        id: u64,
+        vertices: usize,
+    },
+    CreateIndexBuffer {
+        id: u64,
+        indices: usize,
+    },
+    WriteVertices {
+        buffer: u64,
+        vertices: Vec<GpuVertex>,
+    },
+    WriteIndices {
+        buffer: u64,
+        indices: Vec<u32>,
+    },
+    CreateTexture {
+        id: u64,
+        size: [usize; 2],
+    },
+    UpdateTexture {
+        id: u64,
+        pos: [usize; 2],
+        size: [usize; 2],
+    },
+    BeginFrame {
+        screen_size: [u32; 2],
+    },
+    Reapply,
+    EndFrame,
+    BindBuffers {
+        vertices: u64,
+        indices: u64,
+    },
+    SetClip(ClipRect),
+    SetTexture {
+        id: u64,
+        sampler: TextureOptions,
+    },
+    DrawIndexed {
+        base_vertex: usize,
+        vertices: usize,
+        first_index: usize,
+        triangles: usize,
+    },
+    PaintCallback {
+        viewport: Rect,
+        clip_rect: Rect,
+        pixels_per_point: f32,
+    },
+}
+
+/// a [`RenderDevice`] that draws nothing, it only writes down what it was asked to do.
+///
+/// ```
+/// use egui::{Context, RawInput, TextureOptions};
+/// use egui_d3d9::{Command, RecordingDevice, Renderer};
+///
+/// let dev = RecordingDevice::new();
+/// let mut renderer = Renderer::new(&dev, (), 1024, 1024, TextureOptions::LINEAR)?;
+///
+/// let ctx = Context::default();
+/// renderer.run(&dev, &ctx, RawInput::default(), [800, 600], |ctx| {
+///     egui::CentralPanel::default().show(ctx, |ui| ui.label("hello"));
+/// })?;
+/// renderer.draw(&dev, [800, 600], |_| None)?;
+///
+/// assert!(dev.take_commands().contains(&Command::EndFrame));
+/// # Ok::<(), egui_d3d9::Error>(())
+/// ```
+#[derive(Default)]
+pub struct RecordingDevice {
+    commands: RefCell<Vec<Command>>,
+    next_id: Cell<u64>,
+    lost: Cell<bool>,
+}
+
+impl RecordingDevice {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// everything recorded since the last call.
+    pub fn take_commands(&self) -> Vec<Command> {
+        self.commands.take()
+    }
+
+    /// pretend the device is lost, like d3d9 does while a fullscreen game is minimized.
+    pub fn set_lost(&self, lost: bool) {
+        self.lost.set(lost);
+    }
+
+    fn record(&self, command: Command) {
+        self.commands.borrow_mut().push(command);
+    }
+
+    fn next_id(&self) -> u64 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        id
+    }
+
+    fn check_lost(&self) -> Result<()> {
+        if self.lost.get() {
+            Err(Error::DeviceLost)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl RenderDevice for RecordingDevice {
+    type VertexBuffer = u64;
+    type IndexBuffer = u64;
+    type Texture = u64;
+    type Cache = ();
+    type Frame = ();
+
+    fn is_lost(&self) -> bool {
+        self.lost.get()
+    }
+
+    fn create_vertex_buffer(&self, vertices: usize) -> Result<u64> {
+        self.check_lost()?;
+
+        let id = self.next_id();
+        self.record(Command::CreateVertexBuffer { id, vertices });
+        Ok(id)
+    }
+
+    fn create_index_buffer(&self, indices: usize) -> Result<u64> {
+        self.check_lost()?;
+
+        let id = self.next_id();
+        self.record(Command::CreateIndexBuffer { id, indices });
+        Ok(id)
+    }
+
+    fn write_vertices(&self, buffer: &u64, vertices: &[GpuVertex]) -> Result<()> {
+        self.record(Command::WriteVertices {
+            buffer: *buffer,
+            vertices: vertices.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn write_indices(&self, buffer: &u64, indices: &[u32]) -> Result<()> {
+        self.record(Command::WriteIndices {
+            buffer: *buffer,
+            indices: indices.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn create_texture(&self, _pixels: &[TextureColor], size: [usize; 2]) -> Result<u64> {
+        self.check_lost()?;
+
+        let id = self.next_id();
+        self.record(Command::CreateTexture { id, size });
+        Ok(id)
+    }
+
+    fn update_texture(
+        &self,
+        texture: &u64,
+        _pixels: &[TextureColor],
+        pos: [usize; 2],
+        size: [usize; 2],
+    ) -> Result<()> {
+        self.record(Command::UpdateTexture {
+            id: *texture,
+            pos,
+            size,
+        });
+        Ok(())
+    }
+
+    fn invalidate_cache(_cache: &mut ()) {}
+
+    fn begin_frame(&self, _cache: &mut (), screen_size: [u32; 2]) -> Result<()> {
+        self.record(Command::BeginFrame { screen_size });
+        Ok(())
+    }
+
+    fn reapply(&self, _frame: &mut ()) -> Result<()> {
+        self.record(Command::Reapply);
+        Ok(())
+    }
+
+    fn end_frame(&self, _frame: ()) -> Result<()> {
+        self.record(Command::EndFrame);
+        Ok(())
+    }
+
+    fn bind_buffers(&self, vertices: &u64, indices: &u64) -> Result<()> {
+        self.record(Command::BindBuffers {
+            vertices: *vertices,
+            indices: *indices,
+        });
+        Ok(())
+    }
+
+    fn set_clip(&self, clip: ClipRect) -> Result<()> {
+        self.record(Command::SetClip(clip));
+        Ok(())
+    }
+
+    fn set_texture(&self, texture: &u64, sampler: TextureOptions) -> Result<()> {
+        self.record(Command::SetTexture {
+            id: *texture,
+            sampler,
+        });
+        Ok(())
+    }
+
+    fn draw_indexed(
+        &self,
+        base_vertex: usize,
+        vertices: usize,
+        first_index: usize,
+        triangles: usize,
+    ) -> Result<()> {
+        self.record(Command::DrawIndexed {
+            base_vertex,
+            vertices,
+            first_index,
+            triangles,
+        });
+        Ok(())
+    }
+
+    fn paint_callback(&self, _callback: &PaintCallback, info: PaintCallbackInfo) -> Result<bool> {
+        self.record(Command::PaintCallback {
+            viewport: info.viewport,
+            clip_rect: info.clip_rect,
+            pixels_per_point: info.pixels_per_point,
+        });
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use egui::{
+        pos2, vec2, Color32, ColorImage, Context, ImageData, LayerId, RawInput, TextureId,
+        ViewportId,
+    };
+
+    use super::*;
+    use crate::Renderer;
+
+    const SCREEN: [u32; 2] = [200, 100];
+
+    fn renderer(dev: &RecordingDevice, capacity: usize) -> Renderer<RecordingDevice> {
+        Renderer::new(dev, (), capacity, capacity, TextureOptions::LINEAR).unwrap()
+    }
+
+    fn input(pixels_per_point: f32) -> RawInput {
+        let mut input = RawInput {
+            screen_rect: Some(Rect::from_min_size(
+                pos2(0., 0.),
+                vec2(SCREEN[0] as f32, SCREEN[1] as f32) / pixels_per_point,
+            )),
+            ..RawInput::default()
+        };
+        input
+            .viewports
+            .entry(ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(pixels_per_point);
+
+        input
+    }
+
+    /// run and draw one frame, returns what the renderer asked the device to do.
+    fn frame(
+        dev: &RecordingDevice,
+        renderer: &mut Renderer<RecordingDevice>,
+        ctx: &Context,
+        ui: impl FnMut(&Context),
+    ) -> Result<Vec<Command>> {
+        frame_with(dev, renderer, ctx, input(1.), ui)
+    }
+
+    fn frame_with(
+        dev: &RecordingDevice,
+        renderer: &mut Renderer<RecordingDevice>,
+        ctx: &Context,
+        input: RawInput,
+        ui: impl FnMut(&Context),
+    ) -> Result<Vec<Command>> {
+        renderer.run(dev, ctx, input, SCREEN, ui)?;
+        let drawn = renderer.draw(dev, SCREEN, |_| None);
+
+        let commands = dev.take_commands();
+        drawn.map(|()| commands)
+    }
+
+    fn image(color: Color32) -> ImageData {
+        ColorImage::new([4, 4], color).into()
+    }
+
+    /// a rect with `texture` on it, on top of everything and clipped to `clip`.
+    fn textured_rect(ctx: &Context, texture: TextureId, clip: Rect) {
+        ctx.layer_painter(LayerId::debug())
+            .with_clip_rect(clip)
+            .image(
+                texture,
+                Rect::from_min_size(pos2(0., 0.), vec2(50., 50.)),
+                Rect::from_min_max(pos2(0., 0.), pos2(1., 1.)),
+                Color32::WHITE,
+            );
+    }
+
+    /// just the draw calls and what they depend on, without the frame around them.
+    fn draws(commands: &[Command]) -> Vec<&'static str> {
+        commands
+            .iter()
+            .filter_map(|command| {
+                Some(match command {
+                    Command::BindBuffers { .. } => "bind",
+                    Command::SetClip(_) => "clip",
+                    Command::SetTexture { .. } => "texture",
+                    Command::DrawIndexed { .. } => "draw",
+                    Command::PaintCallback { .. } => "callback",
+                    Command::Reapply => "reapply",
+                    _ => return None,
+                })
+            })
+            .collect()
+    }
+
+    fn clips(commands: &[Command]) -> Vec<ClipRect> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::SetClip(clip) => Some(*clip),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frame_is_bracketed() {
+        let dev = RecordingDevice::new();
+        let mut renderer = renderer(&dev, 1024);
+        let ctx = Context::default();
+        dev.take_commands();
+
+        let commands = frame(&dev, &mut renderer, &ctx, |ctx| {
+            textured_rect(ctx, TextureId::default(), Rect::EVERYTHING);
+        })
+        .unwrap();
+
+        let begin = commands
+            .iter()
+            .position(|command| matches!(command, Command::BeginFrame { .. }))
+            .unwrap();
+        assert_eq!(
+            commands[begin],
+            Command::BeginFrame {
+                screen_size: SCREEN
+            }
+        );
+        assert_eq!(commands.last(), Some(&Command::EndFrame));
+
+        // uploads happen before drawing starts.
+        assert!(commands[..begin]
+            .iter()
+            .any(|command| matches!(command, Command::WriteVertices { .. })));
+        assert!(commands[begin..]
+            .iter()
+            .all(|command| !matches!(command, Command::WriteVertices { .. })));
+    }
+
+    fn created(commands: &[Command]) -> Vec<(u64, [usize; 2])> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::CreateTexture { id, size } => Some((*id, *size)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn textures_are_created_updated_and_freed_in_order() {
+        let dev = RecordingDevice::new();
+        let mut renderer = renderer(&dev, 1024);
+        let ctx = Context::default();
+
+        let mut handle = ctx.load_texture("test", image(Color32::RED), TextureOptions::LINEAR);
+        let id = handle.id();
+        let commands = frame(&dev, &mut renderer, &ctx, |ctx| {
+            textured_rect(ctx, id, Rect::EVERYTHING);
+        })
+        .unwrap();
+
+        // the placeholder, the font atlas and ours.
+        let created_now = created(&commands);
+        let (ours, _) = *created_now
+            .iter()
+            .find(|(_, size)| *size == [4, 4])
+            .unwrap();
+        let (placeholder, _) = *created_now
+            .iter()
+            .find(|(_, size)| *size == [1, 1])
+            .unwrap();
+        assert!(commands.contains(&Command::SetTexture {
+            id: ours,
+            sampler: TextureOptions::LINEAR,
+        }));
+
+        // same size, updated in place.
+        handle.set(image(Color32::GREEN), TextureOptions::LINEAR);
+        let commands = frame(&dev, &mut renderer, &ctx, |_| {}).unwrap();
+        assert!(created(&commands).is_empty());
+        assert!(commands.contains(&Command::UpdateTexture {
+            id: ours,
+            pos: [0, 0],
+            size: [4, 4],
+        }));
+
+        handle.set_partial(
+            [1, 2],
+            ColorImage::new([2, 1], Color32::BLUE),
+            TextureOptions::LINEAR,
+        );
+        let commands = frame(&dev, &mut renderer, &ctx, |_| {}).unwrap();
+        assert!(created(&commands).is_empty());
+        assert!(commands.contains(&Command::UpdateTexture {
+            id: ours,
+            pos: [1, 2],
+            size: [2, 1],
+        }));
+
+        // a new size needs a new texture under the same id.
+        handle.set(
+            ColorImage::new([8, 8], Color32::BLUE),
+            TextureOptions::LINEAR,
+        );
+        let commands = frame(&dev, &mut renderer, &ctx, |ctx| {
+            textured_rect(ctx, id, Rect::EVERYTHING);
+        })
+        .unwrap();
+        let [(resized, [8, 8])] = created(&commands)[..] else {
+            panic!("expected one 8x8 texture, got {commands:?}");
+        };
+        assert!(commands.contains(&Command::SetTexture {
+            id: resized,
+            sampler: TextureOptions::LINEAR,
+        }));
+
+        // dropped while the frame still uses it, that frame has to draw it before it's gone.
+        let mut handle = Some(handle);
+        let commands = frame(&dev, &mut renderer, &ctx, |ctx| {
+            textured_rect(ctx, id, Rect::EVERYTHING);
+            handle.take();
+        })
+        .unwrap();
+        assert!(commands.contains(&Command::SetTexture {
+            id: resized,
+            sampler: TextureOptions::LINEAR,
+        }));
+
+        // gone now, drawn white instead of failing the whole frame.
+        let commands = frame(&dev, &mut renderer, &ctx, |ctx| {
+            textured_rect(ctx, id, Rect::EVERYTHING);
+        })
+        .unwrap();
+        assert!(commands.contains(&Command::SetTexture {
+            id: placeholder,
+            sampler: TextureOptions::LINEAR,
+        }));
+        assert!(!commands.contains(&Command::SetTexture {
+            id: resized,
+            sampler: TextureOptions::LINEAR,
+        }));
+    }
+
+    #[test]
+    fn textures_are_sampled_the_way_egui_asks() {
+        let dev = RecordingDevice::new();
+        let mut renderer =
+            Renderer::new(&dev, (), 1024, 1024, TextureOptions::LINEAR_REPEAT).unwrap();
+        let ctx = Context::default();
+
+        let mut handle =
+            ctx.load_texture("pixel art", image(Color32::RED), TextureOptions::NEAREST);
+        let id = handle.id();
+        let commands = frame(&dev, &mut renderer, &ctx, |ctx| {
+            textured_rect(ctx, id, Rect::EVERYTHING);
+            textured_rect(ctx, TextureId::User(7), Rect::EVERYTHING);
+        })
+        .unwrap();
+
+        let samplers = |commands: &[Command]| -> Vec<TextureOptions> {
+            commands
+                .iter()
+                .filter_map(|command| match command {
+                    Command::SetTexture { sampler, .. } => Some(*sampler),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // ours as egui asked, the user's with the default.
+        assert_eq!(
+            samplers(&commands),
+            [TextureOptions::NEAREST, TextureOptions::LINEAR_REPEAT]
+        );
+
+        // egui can change its mind with the next delta.
+        handle.set(image(Color32::RED), TextureOptions::LINEAR);
+        let commands = frame(&dev, &mut renderer, &ctx, |ctx| {
+            textured_rect(ctx, id, Rect::EVERYTHING);
+            textured_rect(ctx, TextureId::User(7), Rect::EVERYTHING);
+        })
+        .unwrap();
+        assert_eq!(
+            samplers(&commands),
+            [TextureOptions::LINEAR, TextureOptions::LINEAR_REPEAT]
+        );
+    }
+
+    #[test]
+    fn clip_rects_are_in_pixels_and_on_screen() {
+        let dev = RecordingDevice::new();
+        let mut renderer = renderer(&dev, 1024);
+        let ctx = Context::default();
+
+        let commands = frame(&dev, &mut renderer, &ctx, |ctx| {
+            textured_rect(
+                ctx,
+                TextureId::default(),
+                Rect::from_min_max(pos2(10., 20.), pos2(30., 40.)),
+            );
+        })
+        .unwrap();
+        assert_eq!(
+            clips(&commands),
+            [ClipRect {
+                left: 10,
+                top: 20,
+                right: 30,
+                bottom: 40,
+            }]
+        );
+
+        // twice the pixels, half the points.
+        let commands = frame_with(&dev, &mut renderer, &ctx, input(2.), |ctx| {
+            textured_rect(
+                ctx,
+                TextureId::default(),
+                Rect::from_min_max(pos2(10., 20.), pos2(30., 40.)),
+            );
+        })
+        .unwrap();
+        assert_eq!(
+            clips(&commands),
+            [ClipRect {
+                left: 20,
+                top: 40,
+                right: 60,
+                bottom: 80,
+            }]
+        );
+
+        // half pixels round outwards, nothing inside the clip rect gets cut off.
+        let commands = frame(&dev, &mut renderer, &ctx, |ctx| {
+            textured_rect(
+                ctx,
+                TextureId::default(),
+                Rect::from_min_max(pos2(10.5, 20.5), pos2(30.5, 40.5)),
+            );
+        })
+        .unwrap();
+        assert_eq!(
+            clips(&commands),
+            [ClipRect {
+                left: 10,
+                top: 20,
+                right: 31,
+                bottom: 41,
+            }]
+        );
+
+        let commands = frame(&dev, &mut renderer, &ctx, |ctx| {
+            textured_rect(
+                ctx,
+                TextureId::default(),
+                Rect::from_min_max(pos2(-10., -10.), pos2(500., 500.)),
+            );
+        })
+        .unwrap();
+        assert_eq!(
+            clips(&commands),
+            [ClipRect {
+                left: 0,
+                top: 0,
+                right: SCREEN[0],
+                bottom: SCREEN[1],
+            }]
+        );
+    }
+
+    #[test]
+    fn callbacks_run_in_order_and_get_our_state_back() {
+        let dev = RecordingDevice::new();
+        let mut renderer = renderer(&dev, 1024);
+        let ctx = Context::default();
+
+        let viewport = Rect::from_min_max(pos2(60., 10.), pos2(90., 40.));
+        let commands = frame(&dev, &mut renderer, &ctx, |ctx| {
+            let painter = ctx.layer_painter(LayerId::debug());
+            painter.rect_filled(
+                Rect::from_min_size(pos2(0., 0.), vec2(50., 50.)),
+                0.,
+                Color32::RED,
+            );
+            painter.add(PaintCallback {
+                rect: viewport,
+                callback: Arc::new(()),
+            });
+            // nothing to draw in there, it never reaches the device.
+            painter.add(PaintCallback {
+                rect: Rect::from_min_size(pos2(10., 10.), vec2(0., 0.)),
+                callback: Arc::new(()),
+            });
+            painter.rect_filled(
+                Rect::from_min_size(pos2(100., 0.), vec2(50., 50.)),
+                0.,
+                Color32::BLUE,
+            );
+        })
+        .unwrap();
+
+        assert_eq!(
+            draws(&commands),
+            [
+                "bind", "clip", "texture", "draw", "callback", "reapply", "bind", "clip",
+                "texture", "draw",
+            ]
+        );
+        assert!(commands.contains(&Command::PaintCallback {
+            viewport,
+            clip_rect: Rect::from_min_size(pos2(0., 0.), vec2(200., 100.)),
+            pixels_per_point: 1.,
+        }));
+    }
+
+    #[test]
+    fn buffers_grow_and_keep_their_size() {
+        let dev = RecordingDevice::new();
+        let mut renderer = renderer(&dev, 16);
+        let ctx = Context::default();
+
+        let commands = dev.take_commands();
+        assert!(commands
+            .iter()
+            .any(|command| matches!(command, Command::CreateVertexBuffer { vertices: 16, .. })));
+        assert!(commands
+            .iter()
+            .any(|command| matches!(command, Command::CreateIndexBuffer { indices: 16, .. })));
+
+        let ui = |ctx: &Context| {
+            egui::CentralPanel::default().show(ctx, |ui| ui.label("more than sixteen vertices"));
+        };
+
+        let commands = frame(&dev, &mut renderer, &ctx, ui).unwrap();
+        let (buffer, written) = commands
+            .iter()
+            .find_map(|command| match command {
+                Command::WriteVertices { buffer, vertices } => Some((*buffer, vertices.len())),
+                _ => None,
+            })
+            .unwrap();
+        assert!(written > 16);
+        // with room to spare, so the next few frames don't need a new one.
+        assert!(commands.contains(&Command::CreateVertexBuffer {
+            id: buffer,
+            vertices: written + 1024,
+        }));
+        assert!(commands.contains(&Command::BindBuffers {
+            vertices: buffer,
+            indices: buffer + 1,
+        }));
+
+        let commands = frame(&dev, &mut renderer, &ctx, ui).unwrap();
+        assert!(!commands
+            .iter()
+            .any(|command| matches!(command, Command::CreateVertexBuffer { .. })));
+        assert!(commands.contains(&Command::BindBuffers {
+            vertices: buffer,
+            indices: buffer + 1,
+        }));
+
+        // after a reset, they start out as big as the last frame needed.
+        renderer.pre_reset();
+        renderer.prepare(&dev).unwrap();
+        assert!(dev.take_commands().contains(&Command::CreateVertexBuffer {
+            id: buffer + 2,
+            vertices: written,
+        }));
+    }
+}
@@ -0,0 +1,336 @@
+use egui::{
+    epaint::{PaintCallbackInfo, Primitive},
+    Context, FullOutput, RawInput, TextureId, TextureOptions,
+};
+
+use crate::{
+    mesh::{Buffers, CallbackDescriptor, GpuVertex, MeshDescriptor, PrimitiveDescriptor},
+    texman::{self, TextureManager},
+    Error, RenderDevice, Result,
+};
+
+/// the platform independent part of the backend: runs egui, uploads the result and draws it.
+///
+/// [`EguiDx9`](crate::EguiDx9) drives one of these with a d3d9 device.
+/// with a [`RecordingDevice`](crate::RecordingDevice), a whole frame runs without windows or a gpu.
+pub struct Renderer<D: RenderDevice> {
+    // get it? tEx-man? tax-man? no?
+    tex_man: TextureManager<D>,
+    buffers: Buffers<D>,
+    prims: Vec<PrimitiveDescriptor>,
+    cache: D::Cache,
+    // for textures that don't come from egui, egui's own bring their options along
+    sampler: TextureOptions,
+    last_idx_capacity: usize,
+    last_vtx_capacity: usize,
+    vertex_capacity: usize,
+    index_capacity: usize,
+    should_reset: bool,
+    // egui textures to free once the current frame is drawn
+    pending_free: Vec<TextureId>,
+
+    vertices: Vec<GpuVertex>,
+    indices: Vec<u32>,
+}
+
+impl<D: RenderDevice> Renderer<D> {
+    /// `sampler` is used for native and user textures, egui says itself how to sample its own.
+    ///
+    /// # Errors
+    /// If buffers cannot be created
+    pub fn new(
+        dev: &D,
+        cache: D::Cache,
+        vertex_capacity: usize,
+        index_capacity: usize,
+        sampler: TextureOptions,
+    ) -> Result<Self> {
+        let mut tex_man = TextureManager::new();
+        tex_man.ensure_placeholder(dev)?;
+
+        Ok(Self {
+            tex_man,
+            buffers: Buffers::create_buffers(dev, vertex_capacity, index_capacity)?,
+            prims: Vec::new(),
+            cache,
+            sampler,
+            last_idx_capacity: 0,
+            last_vtx_capacity: 0,
+            vertex_capacity,
+            index_capacity,
+            should_reset: false,
+            pending_free: Vec::new(),
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        })
+    }
+
+    pub fn cache_mut(&mut self) -> &mut D::Cache {
+        &mut self.cache
+    }
+
+    /// make a texture of your own available to egui.
+    pub fn register_native_texture(&mut self, texture: D::Texture) -> TextureId {
+        self.tex_man.register_native(texture)
+    }
+
+    /// # Errors
+    /// if the id was never registered, or has been freed already.
+    pub fn replace_native_texture(&mut self, id: TextureId, texture: D::Texture) -> Result<()> {
+        self.tex_man.replace_native(id, texture)
+    }
+
+    pub fn free_native_texture(&mut self, id: TextureId) -> Option<D::Texture> {
+        self.tex_man.free_native(id)
+    }
+
+    /// release everything the device would lose on a reset.
+    pub fn pre_reset(&mut self) {
+        self.buffers.delete_buffers();
+        self.tex_man.deallocate_textures();
+        D::invalidate_cache(&mut self.cache);
+
+        self.should_reset = true;
+    }
+
+    /// true until the first [`Renderer::run`] after a reset.
+    pub fn is_reset_pending(&self) -> bool {
+        self.should_reset
+    }
+
+    /// get everything back onto the device if it was reset.
+    ///
+    /// # Errors
+    /// [`Error::DeviceLost`] while the device is lost, nothing can be drawn in that case.
+    pub fn prepare(&mut self, dev: &D) -> Result<()> {
+        if dev.is_lost() {
+            return Err(Error::DeviceLost);
+        }
+
+        if self.should_reset {
+            // start out as big as the last frame needed, no need to grow all over again.
+            self.buffers = Buffers::create_buffers(
+                dev,
+                self.vertex_capacity.max(self.last_vtx_capacity),
+                self.index_capacity.max(self.last_idx_capacity),
+            )?;
+            self.tex_man.reallocate_textures(dev)?;
+        }
+
+        Ok(())
+    }
+
+    /// run egui and upload the result.
+    ///
+    /// returns egui's output, minus the shapes and texture deltas we already took care of.
+    ///
+    /// # Errors
+    /// if uploading textures or geometry failed.
+    pub fn run(
+        &mut self,
+        dev: &D,
+        ctx: &Context,
+        raw_input: RawInput,
+        screen_size: [u32; 2],
+        ui: impl FnMut(&Context),
+    ) -> Result<FullOutput> {
+        let mut output = ctx.run(raw_input, ui);
+
+        let textures_delta = std::mem::take(&mut output.textures_delta);
+        let shapes = std::mem::take(&mut output.shapes);
+
+        self.pending_free.extend_from_slice(&textures_delta.free);
+
+        if !textures_delta.set.is_empty() {
+            self.tex_man.process_set_deltas(dev, &textures_delta)?;
+        }
+
+        // TODO: old code added last len + 512
+        self.vertices.clear();
+        self.indices.clear();
+
+        self.prims = ctx
+            .tessellate(shapes, output.pixels_per_point)
+            .into_iter()
+            .filter_map(|prim| match prim.primitive {
+                Primitive::Mesh(mesh) => {
+                    // most definitely not the rusty way to do this.
+                    // it's ugly, but its efficient.
+                    if let Some((gpumesh, verts, idxs)) = MeshDescriptor::from_mesh(
+                        mesh,
+                        prim.clip_rect,
+                        output.pixels_per_point,
+                        screen_size,
+                    ) {
+                        self.vertices.extend_from_slice(&verts);
+                        self.indices.extend_from_slice(&idxs);
+
+                        Some(PrimitiveDescriptor::Mesh(gpumesh))
+                    } else {
+                        None
+                    }
+                }
+                Primitive::Callback(callback) => {
+                    Some(PrimitiveDescriptor::Callback(CallbackDescriptor {
+                        callback,
+                        clip: prim.clip_rect,
+                        pixels_per_point: output.pixels_per_point,
+                    }))
+                }
+            })
+            .collect();
+
+        self.last_vtx_capacity = self.vertices.len();
+        self.last_idx_capacity = self.indices.len();
+
+        if !self.vertices.is_empty() {
+            self.buffers.update_vertex_buffer(dev, &self.vertices)?;
+            self.buffers.update_index_buffer(dev, &self.indices)?;
+        }
+
+        // only now is everything back on the device.
+        self.should_reset = false;
+
+        Ok(output)
+    }
+
+    /// submit whatever the last run uploaded.
+    ///
+    /// user textures that weren't registered with us are looked up with `resolve_user_texture`,
+    /// anything that can't be resolved, egui's own textures included, is drawn with a plain white texture.
+    ///
+    /// # Errors
+    /// the underlying render error.
+    pub fn draw(
+        &mut self,
+        dev: &D,
+        screen_size: [u32; 2],
+        resolve_user_texture: impl FnMut(u64) -> Option<D::Texture>,
+    ) -> Result<()> {
+        let result = self.draw_prims(dev, screen_size, resolve_user_texture);
+
+        // egui wants these gone only after the frame using them was drawn.
+        self.tex_man.process_free_deltas(&self.pending_free);
+        self.pending_free.clear();
+
+        result
+    }
+
+    fn draw_prims(
+        &mut self,
+        dev: &D,
+        screen_size: [u32; 2],
+        mut resolve_user_texture: impl FnMut(u64) -> Option<D::Texture>,
+    ) -> Result<()> {
+        if self.prims.is_empty() {
+            return Ok(());
+        }
+
+        // back up our state so we don't mess with the game and the game doesn't mess with us.
+        let mut frame = dev.begin_frame(&mut self.cache, screen_size)?;
+
+        let result = self.draw_with(dev, &mut frame, screen_size, &mut resolve_user_texture);
+
+        // give the game its state back, no matter how drawing went.
+        let released = dev.end_frame(frame);
+
+        result.and(released)
+    }
+
+    fn draw_with(
+        &self,
+        dev: &D,
+        frame: &mut D::Frame,
+        screen_size: [u32; 2],
+        resolve_user_texture: &mut impl FnMut(u64) -> Option<D::Texture>,
+    ) -> Result<()> {
+        self.bind_buffers(dev)?;
+
+        let mut our_vtx_idx: usize = 0;
+        let mut our_idx_idx: usize = 0;
+
+        for prim in &self.prims {
+            match prim {
+                PrimitiveDescriptor::Mesh(mesh) => {
+                    dev.set_clip(mesh.clip)?;
+
+                    match mesh.texture_id {
+                        TextureId::Managed(id) => {
+                            match self.tex_man.get_by_id(TextureId::Managed(id)) {
+                                Ok((texture, options)) => dev.set_texture(texture, options)?,
+                                // freed already or never sent, one stale id shouldn't cost the whole frame.
+                                Err(Error::MissingTexture(_)) => {
+                                    dev.set_texture(self.tex_man.placeholder()?, self.sampler)?
+                                }
+                                Err(err) => return Err(err),
+                            }
+                        }
+                        // ours, the user's resolver never sees those ids.
+                        TextureId::User(id) if texman::is_native(id) => {
+                            match self.tex_man.get_native(id) {
+                                Some(texture) => dev.set_texture(texture, self.sampler)?,
+                                None => {
+                                    dev.set_texture(self.tex_man.placeholder()?, self.sampler)?
+                                }
+                            }
+                        }
+                        TextureId::User(id) => match resolve_user_texture(id) {
+                            Some(texture) => dev.set_texture(&texture, self.sampler)?,
+                            None => dev.set_texture(self.tex_man.placeholder()?, self.sampler)?,
+                        },
+                    }
+
+                    dev.draw_indexed(
+                        our_vtx_idx,
+                        mesh.vertices,
+                        our_idx_idx,
+                        mesh.indices / 3usize,
+                    )?;
+
+                    our_vtx_idx += mesh.vertices;
+                    our_idx_idx += mesh.indices;
+                }
+                PrimitiveDescriptor::Callback(desc) => {
+                    let info = PaintCallbackInfo {
+                        viewport: desc.callback.rect,
+                        clip_rect: desc.clip,
+                        pixels_per_point: desc.pixels_per_point,
+                        screen_size_px: screen_size,
+                    };
+
+                    let area = info.viewport_in_pixels();
+
+                    // nothing to draw if the area is empty, and d3d9 rejects empty viewports anyway.
+                    if area.width_px <= 0 || area.height_px <= 0 {
+                        continue;
+                    }
+
+                    // callbacks meant for other backends are skipped, just like egui says.
+                    if dev.paint_callback(&desc.callback, info)? {
+                        // the callback may have changed anything, get our state and buffers back.
+                        dev.reapply(frame)?;
+                        self.bind_buffers(dev)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn bind_buffers(&self, dev: &D) -> Result<()> {
+        // the buffers are only gone while we wait for a reset.
+        let vtx = self.buffers.vtx.as_ref().ok_or(Error::DeviceLost)?;
+        let idx = self.buffers.idx.as_ref().ok_or(Error::DeviceLost)?;
+
+        dev.bind_buffers(vtx, idx)
+    }
+}
+
+impl<D: RenderDevice> Drop for Renderer<D> {
+    fn drop(&mut self) {
+        self.buffers.delete_buffers();
+        self.tex_man.deallocate_textures();
+        D::invalidate_cache(&mut self.cache);
+    }
+}
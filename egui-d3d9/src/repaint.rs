@@ -0,0 +1,118 @@
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use egui::{Context, ViewportId};
+
+/// when egui wants to run again, for reactive mode.
+///
+/// fed by the context's repaint callback, so requests from other threads or from image loaders
+/// finishing after the pass count just like the ones the ui makes.
+#[derive(Clone, Debug, Default)]
+pub struct RepaintSignal {
+    at: Arc<Mutex<Option<Instant>>>,
+}
+
+impl RepaintSignal {
+    /// replaces whatever repaint callback `ctx` had.
+    pub fn install(ctx: &Context) -> Self {
+        let signal = Self::default();
+
+        let callback = signal.clone();
+        ctx.set_request_repaint_callback(move |info| {
+            if info.viewport_id == ViewportId::ROOT {
+                callback.request(info.delay);
+            }
+        });
+
+        signal
+    }
+
+    /// run again once `delay` is over, unless something wanted to run earlier.
+    pub fn request(&self, delay: Duration) {
+        // Duration::MAX means never.
+        let Some(at) = Instant::now().checked_add(delay) else {
+            return;
+        };
+
+        let mut current = self.at.lock().unwrap_or_else(PoisonError::into_inner);
+        if current.is_none_or(|current| at < current) {
+            *current = Some(at);
+        }
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some_and(|at| now >= at)
+    }
+
+    /// forget everything requested so far, right before a run.
+    /// whatever that run asks for arrives through the callback again.
+    pub fn clear(&self) {
+        *self.at.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::RawInput;
+
+    use super::*;
+
+    /// run until egui stops asking for more, it likes a few passes at the start.
+    fn settle(ctx: &Context, signal: &RepaintSignal) {
+        for _ in 0..10 {
+            signal.clear();
+            let _ = ctx.run(RawInput::default(), |_| {});
+
+            if !signal.is_due(Instant::now()) {
+                return;
+            }
+        }
+
+        panic!("egui keeps asking for repaints");
+    }
+
+    #[test]
+    fn requests_from_other_threads_are_seen() {
+        let ctx = Context::default();
+        let signal = RepaintSignal::install(&ctx);
+        settle(&ctx, &signal);
+
+        let other = ctx.clone();
+        std::thread::spawn(move || other.request_repaint())
+            .join()
+            .unwrap();
+
+        assert!(signal.is_due(Instant::now()));
+    }
+
+    #[test]
+    fn delayed_requests_wait() {
+        let ctx = Context::default();
+        let signal = RepaintSignal::install(&ctx);
+        settle(&ctx, &signal);
+
+        ctx.request_repaint_after(Duration::from_secs(60));
+
+        assert!(!signal.is_due(Instant::now()));
+        assert!(signal.is_due(Instant::now() + Duration::from_secs(61)));
+    }
+
+    #[test]
+    fn earliest_request_wins() {
+        let signal = RepaintSignal::default();
+
+        signal.request(Duration::from_secs(60));
+        signal.request(Duration::ZERO);
+        signal.request(Duration::from_secs(30));
+        assert!(signal.is_due(Instant::now()));
+
+        signal.clear();
+        signal.request(Duration::MAX);
+        assert!(!signal.is_due(Instant::now() + Duration::from_secs(3600)));
+    }
+}
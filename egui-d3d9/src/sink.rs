@@ -0,0 +1,225 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use egui::{Context, CursorIcon, Event, Rect};
+use windows::Win32::{
+    Foundation::{LPARAM, WPARAM},
+    UI::WindowsAndMessaging::{HTCLIENT, UNICODE_NOCHAR, WM_SETCURSOR, WM_UNICHAR},
+};
+
+use crate::{
+    inputman::{InputManager, InputResult},
+    platform,
+};
+
+/// what the host should do with a window message after [`InputSink::wnd_proc`] saw it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WndProcResult {
+    /// egui used this message, don't hand it to the game.
+    Consumed,
+    /// forward this message to the game as usual.
+    PassThrough,
+}
+
+impl WndProcResult {
+    #[inline]
+    pub const fn is_consumed(self) -> bool {
+        matches!(self, Self::Consumed)
+    }
+}
+
+/// decides which input messages [`InputSink::wnd_proc`] reports as consumed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputBlocking {
+    /// block all mouse and keyboard input while the overlay is open.
+    WhileOpen,
+    /// only block input egui wants, e.g. clicks on our windows or typing into a text field.
+    #[default]
+    OverEgui,
+    /// never block anything, the game sees every message.
+    Never,
+}
+
+/// where window messages go, for when the window procedure and `present` run on different threads.
+///
+/// get one from [`EguiDx9::input_sink`](crate::EguiDx9::input_sink), clone it into your window procedure
+/// and feed it from there. [`EguiDx9::present`](crate::EguiDx9::present) picks up whatever arrived in the meantime.
+///
+/// ```ignore
+/// static INPUT: OnceLock<InputSink> = OnceLock::new();
+/// let _ = INPUT.set(app.input_sink());
+///
+/// // in the window procedure
+/// if INPUT.get().is_some_and(|input| input.wnd_proc(msg, wparam, lparam).is_consumed()) {
+///     return LRESULT(1);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct InputSink {
+    shared: Arc<Mutex<Shared>>,
+    ctx: Context,
+}
+
+// the whole point of it.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<InputSink>();
+};
+
+/// everything both threads touch.
+pub(crate) struct Shared {
+    pub input_man: InputManager,
+    pub input_blocking: InputBlocking,
+    pub open: bool,
+    // applied on the next WM_SETCURSOR
+    pub cursor_icon: CursorIcon,
+    // where the ime windows go, in client pixels. the input context only works on the window's thread,
+    // so the next message moves them.
+    pub ime_position: Option<Rect>,
+    // same for (un)registering raw input, raw input mode starts once registering worked
+    pub raw_input_request: Option<bool>,
+    // whether the mouse registration is ours, the game's stays when we turn raw input off
+    pub owns_raw_mouse: bool,
+}
+
+impl InputSink {
+    pub(crate) fn new(
+        input_man: InputManager,
+        input_blocking: InputBlocking,
+        ctx: Context,
+    ) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Shared {
+                input_man,
+                input_blocking,
+                open: true,
+                cursor_icon: CursorIcon::Default,
+                ime_position: None,
+                raw_input_request: None,
+                owns_raw_mouse: false,
+            })),
+            ctx,
+        }
+    }
+
+    /// don't call anything that could send the window a message while holding this,
+    /// the window procedure could be waiting for it on the other thread.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Shared> {
+        // nothing in there is left half updated by a panic.
+        self.shared.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// feed a window message to egui.
+    ///
+    /// the result tells you whether to hand the message to the game as well,
+    /// based on the [`InputBlocking`] policy. messages that aren't input always pass through.
+    ///
+    /// we also set the cursor egui wants on `WM_SETCURSOR`. if we consume that one,
+    /// return `TRUE` from your window procedure so nobody changes it back.
+    /// the same goes for `WM_UNICHAR` with `UNICODE_NOCHAR`, that's how windows asks whether we take `WM_UNICHAR`.
+    ///
+    /// touch and pen arrive as `WM_POINTER*`, we ignore the mouse messages windows makes up from them.
+    ///
+    /// this is also where we move the ime windows and (un)register for raw input, which only work from the window's thread.
+    ///
+    /// call this from the thread the window belongs to, i.e. from its window procedure.
+    pub fn wnd_proc(&self, umsg: u32, wparam: WPARAM, lparam: LPARAM) -> WndProcResult {
+        let mut shared = self.lock();
+
+        if umsg == WM_SETCURSOR && shared.wants_cursor(lparam, &self.ctx) {
+            // we draw our own in raw input mode, the real one is somewhere else entirely.
+            let icon = if shared.input_man.is_raw_input() {
+                CursorIcon::None
+            } else {
+                shared.cursor_icon
+            };
+            drop(shared);
+
+            platform::apply_cursor(icon);
+            return WndProcResult::Consumed;
+        }
+
+        if umsg == WM_UNICHAR && wparam.0 as u32 == UNICODE_NOCHAR {
+            return WndProcResult::Consumed;
+        }
+
+        let result = shared.input_man.process(umsg, wparam.0, lparam.0);
+
+        // (re)leasing the capture sends us WM_CAPTURECHANGED right away, which needs the lock again.
+        let capture = shared
+            .input_man
+            .take_capture_request(self.ctx.wants_pointer_input());
+        let ime_position = shared.ime_position.take();
+        let raw_input_request = shared.raw_input_request.take();
+        let release_raw_mouse =
+            raw_input_request == Some(false) && std::mem::take(&mut shared.owns_raw_mouse);
+        let hwnd = shared.input_man.hwnd();
+        let consumed = shared.consumes(&result, &self.ctx);
+        drop(shared);
+
+        if let Some(capture) = capture {
+            platform::set_capture(hwnd, capture);
+        }
+
+        if let Some(rect) = ime_position {
+            platform::set_ime_position(hwnd, rect);
+        }
+
+        // without WM_INPUT we'd have no mouse at all, so it stays off if this fails.
+        if raw_input_request == Some(true) {
+            if let Ok(registered) = platform::register_raw_mouse(hwnd) {
+                let mut shared = self.lock();
+                shared.owns_raw_mouse |= registered;
+                shared.input_man.set_raw_input(true);
+            }
+        }
+
+        if release_raw_mouse {
+            let _ = platform::unregister_raw_mouse();
+        }
+
+        if consumed {
+            WndProcResult::Consumed
+        } else {
+            WndProcResult::PassThrough
+        }
+    }
+
+    /// hand egui an event of your own, e.g. from a controller or a script.
+    ///
+    /// it arrives in the next frame together with everything from the window,
+    /// in reactive mode it also makes sure there is a next frame.
+    /// an empty `Event::Paste` gets filled from the clipboard like a ctrl+v.
+    pub fn push_event(&self, event: Event) {
+        self.lock().input_man.push_event(event);
+    }
+}
+
+impl Shared {
+    fn consumes(&self, result: &InputResult, ctx: &Context) -> bool {
+        // nobody else frees the drop after us.
+        if matches!(result, InputResult::Drop) {
+            return true;
+        }
+
+        match self.input_blocking {
+            InputBlocking::WhileOpen => self.open && (result.is_pointer() || result.is_keyboard()),
+            InputBlocking::OverEgui => {
+                (result.is_pointer() && ctx.wants_pointer_input())
+                    || (result.is_keyboard() && ctx.wants_keyboard_input())
+            }
+            InputBlocking::Never => false,
+        }
+    }
+
+    fn wants_cursor(&self, lparam: LPARAM, ctx: &Context) -> bool {
+        // only inside the client area, the game's borders keep their resize cursors.
+        let hit_test = (lparam.0 & 0xFFFF) as u32;
+
+        hit_test == HTCLIENT
+            && self.open
+            && self.input_blocking != InputBlocking::Never
+            && (self.input_man.is_raw_input()
+                || self.cursor_icon != CursorIcon::Default
+                || ctx.wants_pointer_input())
+    }
+}
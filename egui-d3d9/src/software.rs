@@ -0,0 +1,384 @@
+use std::{cell::RefCell, rc::Rc};
+
+use egui::{
+    epaint::PaintCallbackInfo, Color32, ColorImage, PaintCallback, TextureFilter, TextureOptions,
+    TextureWrapMode,
+};
+
+use crate::{ClipRect, GpuVertex, RenderDevice, Result, TextureColor};
+
+/// a texture living on the cpu, shared between the renderer and the [`SoftwareDevice`].
+pub struct SoftwareTexture {
+    size: [usize; 2],
+    pixels: Vec<TextureColor>,
+}
+
+/// a [`RenderDevice`] that rasterizes on the cpu, the way d3d9 would with our state.
+///
+/// it consumes exactly what we'd upload to the gpu: [`GpuVertex`]es, `u32` indices, the scissor rects,
+/// texture pixels in our bgra layout, and blends with `ONE, INVSRCALPHA`.
+/// meant as a reference to compare against, see [`compare_golden`](crate::compare_golden).
+///
+/// paint callbacks are d3d9 specific and skipped.
+#[derive(Default)]
+pub struct SoftwareDevice {
+    target: RefCell<Framebuffer>,
+    state: RefCell<DrawState>,
+}
+
+#[derive(Default)]
+struct Framebuffer {
+    size: [usize; 2],
+    // rgba, premultiplied. just like the backbuffer, once we blended into it.
+    pixels: Vec<[u8; 4]>,
+}
+
+#[derive(Default)]
+struct DrawState {
+    vertices: Option<Rc<RefCell<Vec<GpuVertex>>>>,
+    indices: Option<Rc<RefCell<Vec<u32>>>>,
+    texture: Option<Rc<RefCell<SoftwareTexture>>>,
+    clip: ClipRect,
+    sampler: TextureOptions,
+}
+
+impl SoftwareDevice {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// start over with a target `size` pixels big, filled with `color`.
+    /// that's what the game leaves for us to draw on top of.
+    pub fn clear(&self, size: [usize; 2], color: Color32) {
+        let mut target = self.target.borrow_mut();
+        target.size = size;
+        target.pixels = vec![color.to_array(); size[0] * size[1]];
+    }
+
+    /// what we drew so far. the target has the size of the last frame.
+    pub fn image(&self) -> ColorImage {
+        let target = self.target.borrow();
+
+        ColorImage {
+            size: target.size,
+            pixels: target
+                .pixels
+                .iter()
+                .map(|[r, g, b, a]| Color32::from_rgba_premultiplied(*r, *g, *b, *a))
+                .collect(),
+        }
+    }
+}
+
+impl RenderDevice for SoftwareDevice {
+    type VertexBuffer = Rc<RefCell<Vec<GpuVertex>>>;
+    type IndexBuffer = Rc<RefCell<Vec<u32>>>;
+    type Texture = Rc<RefCell<SoftwareTexture>>;
+    type Cache = ();
+    type Frame = ();
+
+    fn is_lost(&self) -> bool {
+        false
+    }
+
+    fn create_vertex_buffer(&self, vertices: usize) -> Result<Self::VertexBuffer> {
+        Ok(Rc::new(RefCell::new(Vec::with_capacity(vertices))))
+    }
+
+    fn create_index_buffer(&self, indices: usize) -> Result<Self::IndexBuffer> {
+        Ok(Rc::new(RefCell::new(Vec::with_capacity(indices))))
+    }
+
+    fn write_vertices(&self, buffer: &Self::VertexBuffer, vertices: &[GpuVertex]) -> Result<()> {
+        let mut buffer = buffer.borrow_mut();
+        buffer.clear();
+        buffer.extend_from_slice(vertices);
+        Ok(())
+    }
+
+    fn write_indices(&self, buffer: &Self::IndexBuffer, indices: &[u32]) -> Result<()> {
+        let mut buffer = buffer.borrow_mut();
+        buffer.clear();
+        buffer.extend_from_slice(indices);
+        Ok(())
+    }
+
+    fn create_texture(&self, pixels: &[TextureColor], size: [usize; 2]) -> Result<Self::Texture> {
+        Ok(Rc::new(RefCell::new(SoftwareTexture {
+            size,
+            pixels: pixels.to_vec(),
+        })))
+    }
+
+    fn update_texture(
+        &self,
+        texture: &Self::Texture,
+        pixels: &[TextureColor],
+        pos: [usize; 2],
+        size: [usize; 2],
+    ) -> Result<()> {
+        let mut texture = texture.borrow_mut();
+        let width = texture.size[0];
+
+        for (row, src) in pixels.chunks_exact(size[0].max(1)).enumerate() {
+            let start = (pos[1] + row) * width + pos[0];
+            texture.pixels[start..start + size[0]].copy_from_slice(src);
+        }
+
+        Ok(())
+    }
+
+    fn invalidate_cache(_cache: &mut ()) {}
+
+    fn begin_frame(&self, _cache: &mut (), screen_size: [u32; 2]) -> Result<()> {
+        let size = [screen_size[0] as usize, screen_size[1] as usize];
+
+        let mut target = self.target.borrow_mut();
+        if target.size != size {
+            target.size = size;
+            target.pixels = vec![[0; 4]; size[0] * size[1]];
+        }
+
+        let mut state = self.state.borrow_mut();
+        state.clip = ClipRect {
+            left: 0,
+            top: 0,
+            right: screen_size[0],
+            bottom: screen_size[1],
+        };
+
+        Ok(())
+    }
+
+    fn reapply(&self, _frame: &mut ()) -> Result<()> {
+        Ok(())
+    }
+
+    fn end_frame(&self, _frame: ()) -> Result<()> {
+        *self.state.borrow_mut() = DrawState::default();
+        Ok(())
+    }
+
+    fn bind_buffers(
+        &self,
+        vertices: &Self::VertexBuffer,
+        indices: &Self::IndexBuffer,
+    ) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        state.vertices = Some(vertices.clone());
+        state.indices = Some(indices.clone());
+        Ok(())
+    }
+
+    fn set_clip(&self, clip: ClipRect) -> Result<()> {
+        self.state.borrow_mut().clip = clip;
+        Ok(())
+    }
+
+    fn set_texture(&self, texture: &Self::Texture, sampler: TextureOptions) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        state.texture = Some(texture.clone());
+        state.sampler = sampler;
+        Ok(())
+    }
+
+    fn draw_indexed(
+        &self,
+        base_vertex: usize,
+        _vertices: usize,
+        first_index: usize,
+        triangles: usize,
+    ) -> Result<()> {
+        let state = self.state.borrow();
+        let (Some(vertices), Some(indices), Some(texture)) =
+            (&state.vertices, &state.indices, &state.texture)
+        else {
+            // d3d9 wouldn't draw anything sensible either.
+            return Ok(());
+        };
+
+        let vertices = vertices.borrow();
+        let indices = indices.borrow();
+        let texture = texture.borrow();
+        let mut target = self.target.borrow_mut();
+
+        for triangle in indices[first_index..first_index + triangles * 3].chunks_exact(3) {
+            let corners = [0, 1, 2].map(|i| vertices[base_vertex + triangle[i] as usize]);
+
+            target.draw_triangle(corners, &texture, state.sampler, state.clip);
+        }
+
+        Ok(())
+    }
+
+    fn paint_callback(&self, _callback: &PaintCallback, _info: PaintCallbackInfo) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+impl Framebuffer {
+    fn draw_triangle(
+        &mut self,
+        mut corners: [GpuVertex; 3],
+        texture: &SoftwareTexture,
+        sampler: TextureOptions,
+        clip: ClipRect,
+    ) {
+        let pos = |v: &GpuVertex| [v.pos[0], v.pos[1]];
+
+        let mut area = edge(pos(&corners[0]), pos(&corners[1]), pos(&corners[2]));
+        if area == 0. {
+            return;
+        }
+
+        // we don't cull, so just bring everything into the same winding.
+        if area < 0. {
+            corners.swap(1, 2);
+            area = -area;
+        }
+
+        let [p0, p1, p2] = [pos(&corners[0]), pos(&corners[1]), pos(&corners[2])];
+
+        // more texels than pixels means we are minifying.
+        let uv_area = edge(
+            corners[0].uv.into(),
+            corners[1].uv.into(),
+            corners[2].uv.into(),
+        )
+        .abs()
+            * (texture.size[0] * texture.size[1]) as f32;
+        let filter = if uv_area > area {
+            sampler.minification
+        } else {
+            sampler.magnification
+        };
+
+        let clip_right = (clip.right as usize).min(self.size[0]);
+        let clip_bottom = (clip.bottom as usize).min(self.size[1]);
+
+        let min_x = p0[0].min(p1[0]).min(p2[0]).floor().max(clip.left as f32) as usize;
+        let min_y = p0[1].min(p1[1]).min(p2[1]).floor().max(clip.top as f32) as usize;
+        let max_x = (p0[0].max(p1[0]).max(p2[0]).ceil().max(0.) as usize).min(clip_right);
+        let max_y = (p0[1].max(p1[1]).max(p2[1]).ceil().max(0.) as usize).min(clip_bottom);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                // our projection puts pixel centers on .5, same as egui.
+                let center = [x as f32 + 0.5, y as f32 + 0.5];
+
+                let w0 = edge(p1, p2, center);
+                let w1 = edge(p2, p0, center);
+                let w2 = edge(p0, p1, center);
+
+                if !covers(w0, p1, p2) || !covers(w1, p2, p0) || !covers(w2, p0, p1) {
+                    continue;
+                }
+
+                let weights = [w0 / area, w1 / area, w2 / area];
+
+                let uv = [
+                    interpolate(weights, corners.map(|v| v.uv.x)),
+                    interpolate(weights, corners.map(|v| v.uv.y)),
+                ];
+                let diffuse = [
+                    interpolate(weights, corners.map(|v| f32::from(v.color.r) / 255.)),
+                    interpolate(weights, corners.map(|v| f32::from(v.color.g) / 255.)),
+                    interpolate(weights, corners.map(|v| f32::from(v.color.b) / 255.)),
+                    interpolate(weights, corners.map(|v| f32::from(v.color.a) / 255.)),
+                ];
+
+                let texel = texture.sample(uv, filter, sampler.wrap_mode);
+
+                // D3DTOP_MODULATE on color and alpha
+                let src = [0, 1, 2, 3].map(|i| diffuse[i] * texel[i]);
+
+                // D3DBLEND_ONE, D3DBLEND_INVSRCALPHA, for color and alpha
+                let dst = &mut self.pixels[y * self.size[0] + x];
+                *dst = [0, 1, 2, 3].map(|i| {
+                    let blended = src[i] + f32::from(dst[i]) / 255. * (1. - src[3]);
+                    (blended * 255.).round().clamp(0., 255.) as u8
+                });
+            }
+        }
+    }
+}
+
+impl SoftwareTexture {
+    /// rgba in 0..1, sampled like d3d9 does it: texel centers on .5.
+    fn sample(&self, uv: [f32; 2], filter: TextureFilter, wrap: TextureWrapMode) -> [f32; 4] {
+        let x = uv[0] * self.size[0] as f32;
+        let y = uv[1] * self.size[1] as f32;
+
+        match filter {
+            TextureFilter::Nearest => self.texel(x.floor() as isize, y.floor() as isize, wrap),
+            TextureFilter::Linear => {
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as isize, y0 as isize);
+
+                let top = lerp(self.texel(x0, y0, wrap), self.texel(x0 + 1, y0, wrap), fx);
+                let bottom = lerp(
+                    self.texel(x0, y0 + 1, wrap),
+                    self.texel(x0 + 1, y0 + 1, wrap),
+                    fx,
+                );
+
+                lerp(top, bottom, fy)
+            }
+        }
+    }
+
+    fn texel(&self, x: isize, y: isize, wrap: TextureWrapMode) -> [f32; 4] {
+        let x = address(x, self.size[0], wrap);
+        let y = address(y, self.size[1], wrap);
+
+        let TextureColor { r, g, b, a } = self.pixels[y * self.size[0] + x];
+        [r, g, b, a].map(|c| f32::from(c) / 255.)
+    }
+}
+
+fn address(coord: isize, size: usize, wrap: TextureWrapMode) -> usize {
+    let size = size as isize;
+
+    let coord = match wrap {
+        TextureWrapMode::ClampToEdge => coord.clamp(0, size - 1),
+        TextureWrapMode::Repeat => coord.rem_euclid(size),
+        TextureWrapMode::MirroredRepeat => {
+            let coord = coord.rem_euclid(size * 2);
+            if coord < size {
+                coord
+            } else {
+                size * 2 - 1 - coord
+            }
+        }
+    };
+
+    coord as usize
+}
+
+/// twice the signed area of `a, b, c`, positive if they are clockwise on screen.
+fn edge(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// d3d's top-left rule, so pixels on an edge shared by two triangles are only drawn once.
+fn covers(weight: f32, a: [f32; 2], b: [f32; 2]) -> bool {
+    if weight != 0. {
+        return weight > 0.;
+    }
+
+    let top = a[1] == b[1] && b[0] > a[0];
+    let left = b[1] < a[1];
+
+    top || left
+}
+
+fn interpolate(weights: [f32; 3], values: [f32; 3]) -> f32 {
+    weights[0] * values[0] + weights[1] * values[1] + weights[2] * values[2]
+}
+
+fn lerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [0, 1, 2, 3].map(|i| a[i] + (b[i] - a[i]) * t)
+}
@@ -1,40 +1,198 @@
+use egui::{TextureFilter, TextureOptions, TextureWrapMode};
 use windows::{
     Foundation::Numerics::Matrix4x4,
-    Win32::Graphics::Direct3D9::{
-        IDirect3DDevice9, IDirect3DStateBlock9, IDirect3DSurface9, D3DBACKBUFFER_TYPE_MONO,
-        D3DBLENDOP_ADD, D3DBLEND_INVSRCALPHA, D3DBLEND_ONE, D3DCULL_NONE, D3DFILL_SOLID,
-        D3DMULTISAMPLE_TYPE, D3DRS_ALPHABLENDENABLE, D3DRS_ALPHATESTENABLE, D3DRS_BLENDOP,
-        D3DRS_BLENDOPALPHA, D3DRS_CLIPPING, D3DRS_COLORWRITEENABLE, D3DRS_CULLMODE,
-        D3DRS_DESTBLEND, D3DRS_DESTBLENDALPHA, D3DRS_FILLMODE, D3DRS_FOGENABLE, D3DRS_LASTPIXEL,
-        D3DRS_LIGHTING, D3DRS_RANGEFOGENABLE, D3DRS_SCISSORTESTENABLE,
-        D3DRS_SEPARATEALPHABLENDENABLE, D3DRS_SHADEMODE, D3DRS_SPECULARENABLE, D3DRS_SRCBLEND,
-        D3DRS_SRCBLENDALPHA, D3DRS_SRGBWRITEENABLE, D3DRS_STENCILENABLE, D3DRS_TEXTUREFACTOR,
-        D3DRS_ZENABLE, D3DRS_ZWRITEENABLE, D3DSAMP_ADDRESSU, D3DSAMP_ADDRESSV, D3DSAMP_ADDRESSW,
-        D3DSAMP_BORDERCOLOR, D3DSAMP_MAGFILTER, D3DSAMP_MINFILTER, D3DSAMP_MIPFILTER, D3DSBT_ALL,
-        D3DSHADE_GOURAUD, D3DSURFACE_DESC, D3DTADDRESS_CLAMP, D3DTA_CURRENT, D3DTA_DIFFUSE,
-        D3DTA_TEXTURE, D3DTEXF_LINEAR, D3DTEXF_NONE, D3DTOP_DISABLE, D3DTOP_MODULATE,
-        D3DTRANSFORMSTATETYPE, D3DTSS_ALPHAARG0, D3DTSS_ALPHAARG1, D3DTSS_ALPHAARG2,
-        D3DTSS_ALPHAOP, D3DTSS_COLORARG0, D3DTSS_COLORARG1, D3DTSS_COLORARG2, D3DTSS_COLOROP,
-        D3DTS_PROJECTION, D3DTS_VIEW, D3DVIEWPORT9,
+    Win32::{
+        Foundation::E_FAIL,
+        Graphics::Direct3D9::{
+            IDirect3DDevice9, IDirect3DStateBlock9, IDirect3DSurface9, D3DBACKBUFFER_TYPE_MONO,
+            D3DBLENDOP_ADD, D3DBLEND_INVSRCALPHA, D3DBLEND_ONE, D3DCULL_NONE, D3DFILL_SOLID,
+            D3DFORMAT, D3DMULTISAMPLE_TYPE, D3DRS_ALPHABLENDENABLE, D3DRS_ALPHATESTENABLE,
+            D3DRS_BLENDOP, D3DRS_BLENDOPALPHA, D3DRS_CLIPPING, D3DRS_COLORWRITEENABLE,
+            D3DRS_CULLMODE, D3DRS_DESTBLEND, D3DRS_DESTBLENDALPHA, D3DRS_FILLMODE, D3DRS_FOGENABLE,
+            D3DRS_LASTPIXEL, D3DRS_LIGHTING, D3DRS_RANGEFOGENABLE, D3DRS_SCISSORTESTENABLE,
+            D3DRS_SEPARATEALPHABLENDENABLE, D3DRS_SHADEMODE, D3DRS_SPECULARENABLE, D3DRS_SRCBLEND,
+            D3DRS_SRCBLENDALPHA, D3DRS_SRGBWRITEENABLE, D3DRS_STENCILENABLE, D3DRS_TEXTUREFACTOR,
+            D3DRS_ZENABLE, D3DRS_ZWRITEENABLE, D3DSAMP_ADDRESSU, D3DSAMP_ADDRESSV,
+            D3DSAMP_ADDRESSW, D3DSAMP_BORDERCOLOR, D3DSAMP_MAGFILTER, D3DSAMP_MINFILTER,
+            D3DSAMP_MIPFILTER, D3DSBT_ALL, D3DSHADE_GOURAUD, D3DSURFACE_DESC, D3DTADDRESS_CLAMP,
+            D3DTADDRESS_MIRROR, D3DTADDRESS_WRAP, D3DTA_CURRENT, D3DTA_DIFFUSE, D3DTA_TEXTURE,
+            D3DTEXF_LINEAR, D3DTEXF_NONE, D3DTEXF_POINT, D3DTEXTUREADDRESS, D3DTEXTUREFILTERTYPE,
+            D3DTOP_DISABLE, D3DTOP_MODULATE, D3DTRANSFORMSTATETYPE, D3DTSS_ALPHAARG0,
+            D3DTSS_ALPHAARG1, D3DTSS_ALPHAARG2, D3DTSS_ALPHAOP, D3DTSS_COLORARG0, D3DTSS_COLORARG1,
+            D3DTSS_COLORARG2, D3DTSS_COLOROP, D3DTS_PROJECTION, D3DTS_VIEW, D3DVIEWPORT9,
+        },
     },
 };
 
-use crate::mesh::FVF_CUSTOMVERTEX;
+use crate::d3d9::FVF_CUSTOMVERTEX;
+
+/// where we draw egui.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderTarget {
+    /// straight into the backbuffer. the cheapest option, and what you want almost always.
+    #[default]
+    Direct,
+    /// into a copy of the backbuffer, which is copied back once we're done.
+    /// costs two full-screen copies per frame, but some games misbehave if we touch the backbuffer directly.
+    Intermediate,
+}
+
+/// everything that outlives a single frame.
+pub struct StateCache {
+    render_target: RenderTarget,
+    intermediate: Option<Intermediate>,
+    // the game's state, re-captured every frame.
+    backup: Option<IDirect3DStateBlock9>,
+    // our state, recorded once and only re-recorded if the viewport changes.
+    egui: Option<EguiState>,
+}
+
+struct EguiState {
+    block: IDirect3DStateBlock9,
+    viewport: D3DVIEWPORT9,
+}
+
+struct Intermediate {
+    surface: IDirect3DSurface9,
+    width: u32,
+    height: u32,
+    format: D3DFORMAT,
+}
+
+impl StateCache {
+    pub fn new(render_target: RenderTarget) -> Self {
+        Self {
+            render_target,
+            intermediate: None,
+            backup: None,
+            egui: None,
+        }
+    }
+
+    pub fn set_render_target(&mut self, render_target: RenderTarget) {
+        self.render_target = render_target;
+        self.intermediate = None;
+    }
+
+    /// drop everything living in `D3DPOOL_DEFAULT`, has to happen before a reset.
+    /// state blocks go too, they hold on to whatever was bound when they were captured.
+    pub fn invalidate(&mut self) {
+        self.intermediate = None;
+        self.backup = None;
+        self.egui = None;
+    }
+
+    fn backup(&mut self, dev: &IDirect3DDevice9) -> windows::core::Result<IDirect3DStateBlock9> {
+        if let Some(backup) = &self.backup {
+            return Ok(backup.clone());
+        }
+
+        let backup = unsafe { dev.CreateStateBlock(D3DSBT_ALL)? };
+        self.backup = Some(backup.clone());
+
+        Ok(backup)
+    }
+
+    /// our state as a state block, so setting it is a single `Apply`.
+    fn egui_state(
+        &mut self,
+        dev: &IDirect3DDevice9,
+        viewport: D3DVIEWPORT9,
+    ) -> windows::core::Result<IDirect3DStateBlock9> {
+        if let Some(state) = &self.egui {
+            if state.viewport == viewport {
+                return Ok(state.block.clone());
+            }
+        }
+
+        let block = unsafe {
+            dev.BeginStateBlock()?;
+
+            // always end recording, even if something went wrong halfway through.
+            let recorded = setup_state(dev, viewport);
+            let block = dev.EndStateBlock();
+            recorded?;
+
+            block?
+        };
+
+        self.egui = Some(EguiState {
+            block: block.clone(),
+            viewport,
+        });
+
+        Ok(block)
+    }
+
+    /// our intermediate surface, only re-created if the backbuffer changed.
+    fn intermediate(
+        &mut self,
+        dev: &IDirect3DDevice9,
+        backbuffer: &IDirect3DSurface9,
+    ) -> windows::core::Result<IDirect3DSurface9> {
+        let mut desc = D3DSURFACE_DESC::default();
+        unsafe { backbuffer.GetDesc(&mut desc)? };
+
+        if let Some(intermediate) = &self.intermediate {
+            if intermediate.width == desc.Width
+                && intermediate.height == desc.Height
+                && intermediate.format == desc.Format
+            {
+                return Ok(intermediate.surface.clone());
+            }
+        }
+
+        let mut surface: Option<IDirect3DSurface9> = None;
+
+        unsafe {
+            dev.CreateRenderTarget(
+                desc.Width,
+                desc.Height,
+                desc.Format,
+                D3DMULTISAMPLE_TYPE(0),
+                0,
+                true,
+                &mut surface,
+                std::ptr::null_mut(),
+            )?;
+        }
+
+        let surface = surface.ok_or_else(|| windows::core::Error::from(E_FAIL))?;
+
+        self.intermediate = Some(Intermediate {
+            surface: surface.clone(),
+            width: desc.Width,
+            height: desc.Height,
+            format: desc.Format,
+        });
+
+        Ok(surface)
+    }
+}
 
 pub struct DxState {
     original_state: IDirect3DStateBlock9,
     original_world: Matrix4x4,
     original_view: Matrix4x4,
     original_proj: Matrix4x4,
+    original_target: IDirect3DSurface9,
     backbuffer: IDirect3DSurface9,
+    // what we draw into, either the backbuffer or our intermediate surface
+    target: IDirect3DSurface9,
+    egui_state: IDirect3DStateBlock9,
     dev: IDirect3DDevice9,
+    released: bool,
 }
 
 impl DxState {
-    pub fn setup(dev: &IDirect3DDevice9, viewport: D3DVIEWPORT9) -> windows::core::Result<Self> {
+    pub fn setup(
+        dev: &IDirect3DDevice9,
+        cache: &mut StateCache,
+        viewport: D3DVIEWPORT9,
+    ) -> windows::core::Result<Self> {
         unsafe {
             // backup state
-            let original_state = dev.CreateStateBlock(D3DSBT_ALL)?;
+            let original_state = cache.backup(dev)?;
 
             original_state.Capture()?;
 
@@ -48,23 +206,64 @@ impl DxState {
 
             dev.GetTransform(D3DTS_PROJECTION, &mut original_proj)?;
 
+            // state blocks don't cover render targets.
+            let original_target = dev.GetRenderTarget(0)?;
+
             let backbuffer = dev.GetBackBuffer(0, 0, D3DBACKBUFFER_TYPE_MONO)?;
 
-            // set our desired state
-            setup_state(dev, viewport)?;
+            let target = match cache.render_target {
+                RenderTarget::Direct => backbuffer.clone(),
+                RenderTarget::Intermediate => {
+                    let surface = cache.intermediate(dev, &backbuffer)?;
+
+                    dev.StretchRect(
+                        &backbuffer,
+                        std::ptr::null(),
+                        &surface,
+                        std::ptr::null(),
+                        D3DTEXF_NONE,
+                    )?;
+
+                    surface
+                }
+            };
+
+            // set our desired state.
+            // setting the render target resets the viewport, so it has to come first.
+            let egui_state = cache.egui_state(dev, viewport)?;
+
+            dev.SetRenderTarget(0, &target)?;
+            egui_state.Apply()?;
 
             Ok(Self {
                 original_state,
                 original_world,
                 original_view,
                 original_proj,
+                original_target,
                 backbuffer,
+                target,
+                egui_state,
                 dev: dev.clone(),
+                released: false,
             })
         }
     }
 
+    /// re-apply our state after something else (e.g. a paint callback) touched the device.
+    pub fn reapply(&self) -> windows::core::Result<()> {
+        unsafe {
+            self.dev.SetRenderTarget(0, &self.target)?;
+            self.egui_state.Apply()
+        }
+    }
+
     pub fn release(&mut self) -> windows::core::Result<()> {
+        if self.released {
+            return Ok(());
+        }
+        self.released = true;
+
         // restore the previous state
         unsafe {
             self.dev
@@ -73,19 +272,17 @@ impl DxState {
             self.dev
                 .SetTransform(D3DTS_PROJECTION, &self.original_proj)?;
 
-            let backbuffer = self.dev.GetBackBuffer(0, 0, D3DBACKBUFFER_TYPE_MONO)?;
+            if self.target != self.backbuffer {
+                self.dev.StretchRect(
+                    &self.target,
+                    std::ptr::null(),
+                    &self.backbuffer,
+                    std::ptr::null(),
+                    D3DTEXF_NONE,
+                )?;
+            }
 
-            let render_target = self.dev.GetRenderTarget(0)?;
-
-            self.dev.StretchRect(
-                &render_target,
-                std::ptr::null(),
-                &backbuffer,
-                std::ptr::null(),
-                D3DTEXF_NONE,
-            )?;
-
-            self.dev.SetRenderTarget(0, &self.backbuffer)?;
+            self.dev.SetRenderTarget(0, &self.original_target)?;
 
             self.original_state.Apply()?;
         }
@@ -96,7 +293,9 @@ impl DxState {
 
 impl Drop for DxState {
     fn drop(&mut self) {
-        self.release().expect("unable to release state");
+        // we only get here without an explicit release if rendering bailed out early,
+        // and we are already reporting that error.
+        let _ = self.release();
     }
 }
 
@@ -104,36 +303,6 @@ impl Drop for DxState {
 fn setup_state(dev: &IDirect3DDevice9, viewport: D3DVIEWPORT9) -> windows::core::Result<()> {
     unsafe {
         // general set up
-        let backbuffer: IDirect3DSurface9 = dev.GetBackBuffer(0, 0, D3DBACKBUFFER_TYPE_MONO)?;
-
-        let mut desc = D3DSURFACE_DESC::default();
-        backbuffer.GetDesc(&mut desc)?;
-
-        let mut surface: Option<IDirect3DSurface9> = None;
-
-        dev.CreateRenderTarget(
-            desc.Width,
-            desc.Height,
-            desc.Format,
-            D3DMULTISAMPLE_TYPE(0),
-            0,
-            true,
-            &mut surface,
-            std::ptr::null_mut(),
-        )?;
-
-        let surface = surface.expect("unable to create surface");
-
-        dev.SetRenderTarget(0, &surface)?;
-
-        dev.StretchRect(
-            &backbuffer,
-            std::ptr::null(),
-            &surface,
-            std::ptr::null(),
-            D3DTEXF_NONE,
-        )?;
-
         dev.SetViewport(&viewport)?;
 
         // set up fvf
@@ -221,15 +390,50 @@ fn setup_state(dev: &IDirect3DDevice9, viewport: D3DVIEWPORT9) -> windows::core:
         dev.SetTextureStageState(2, D3DTSS_COLOROP, D3DTOP_DISABLE.0 as _)?;
         dev.SetTextureStageState(2, D3DTSS_ALPHAOP, D3DTOP_DISABLE.0 as _)?;
 
-        // set up sampler
-        dev.SetSamplerState(0, D3DSAMP_MINFILTER, D3DTEXF_LINEAR.0 as _)?;
-        dev.SetSamplerState(0, D3DSAMP_MIPFILTER, D3DTEXF_LINEAR.0 as _)?;
-        dev.SetSamplerState(0, D3DSAMP_MAGFILTER, D3DTEXF_LINEAR.0 as _)?;
+        // set up sampler, filtering and wrapping come with every texture.
         dev.SetSamplerState(0, D3DSAMP_BORDERCOLOR, 0xFFFF_FFFF)?;
-        dev.SetSamplerState(0, D3DSAMP_ADDRESSU, D3DTADDRESS_CLAMP.0 as _)?;
-        dev.SetSamplerState(0, D3DSAMP_ADDRESSV, D3DTADDRESS_CLAMP.0 as _)?;
-        dev.SetSamplerState(0, D3DSAMP_ADDRESSW, D3DTADDRESS_CLAMP.0 as _)?;
+        set_sampler(dev, TextureOptions::LINEAR)?;
 
         Ok(())
     }
 }
+
+/// filtering and wrapping for whatever is bound next, every texture can want its own.
+pub fn set_sampler(dev: &IDirect3DDevice9, sampler: TextureOptions) -> windows::core::Result<()> {
+    let address = texture_address(sampler.wrap_mode);
+    let mip_filter = sampler.mipmap_mode.map_or(D3DTEXF_NONE, texture_filter);
+
+    unsafe {
+        dev.SetSamplerState(
+            0,
+            D3DSAMP_MINFILTER,
+            texture_filter(sampler.minification).0 as _,
+        )?;
+        dev.SetSamplerState(0, D3DSAMP_MIPFILTER, mip_filter.0 as _)?;
+        dev.SetSamplerState(
+            0,
+            D3DSAMP_MAGFILTER,
+            texture_filter(sampler.magnification).0 as _,
+        )?;
+        dev.SetSamplerState(0, D3DSAMP_ADDRESSU, address.0 as _)?;
+        dev.SetSamplerState(0, D3DSAMP_ADDRESSV, address.0 as _)?;
+        dev.SetSamplerState(0, D3DSAMP_ADDRESSW, address.0 as _)?;
+    }
+
+    Ok(())
+}
+
+const fn texture_filter(filter: TextureFilter) -> D3DTEXTUREFILTERTYPE {
+    match filter {
+        TextureFilter::Nearest => D3DTEXF_POINT,
+        TextureFilter::Linear => D3DTEXF_LINEAR,
+    }
+}
+
+const fn texture_address(wrap: TextureWrapMode) -> D3DTEXTUREADDRESS {
+    match wrap {
+        TextureWrapMode::ClampToEdge => D3DTADDRESS_CLAMP,
+        TextureWrapMode::Repeat => D3DTADDRESS_WRAP,
+        TextureWrapMode::MirroredRepeat => D3DTADDRESS_MIRROR,
+    }
+}
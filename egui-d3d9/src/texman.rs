@@ -1,16 +1,11 @@
 use std::collections::HashMap;
 
-use egui::{ImageData, TextureId, TexturesDelta};
-use windows::Win32::{
-    Foundation::{POINT, RECT},
-    Graphics::Direct3D9::{
-        IDirect3DDevice9, IDirect3DTexture9, D3DFMT_A8R8G8B8, D3DLOCKED_RECT, D3DLOCK_DISCARD,
-        D3DLOCK_READONLY, D3DPOOL_DEFAULT, D3DPOOL_SYSTEMMEM, D3DUSAGE_DYNAMIC,
-    },
-};
+use egui::{epaint::ImageDelta, ImageData, TextureId, TextureOptions, TexturesDelta};
+
+use crate::{Error, RenderDevice, Result};
 
 #[repr(C)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct TextureColor {
     pub b: u8,
     pub g: u8,
@@ -18,108 +13,169 @@ pub struct TextureColor {
     pub a: u8,
 }
 
-struct ManagedTexture {
-    handle: Option<IDirect3DTexture9>,
+struct ManagedTexture<T> {
+    handle: Option<T>,
     pixels: Vec<TextureColor>,
     size: [usize; 2],
+    // how egui wants this one sampled, from its latest delta
+    options: TextureOptions,
 }
 
-impl ManagedTexture {
-    pub fn handle(&self) -> &IDirect3DTexture9 {
-        self.handle
-            .as_ref()
-            .expect("unable to get texture handle")
+impl<T> ManagedTexture<T> {
+    pub fn handle(&self) -> Result<&T> {
+        // textures only lose their handle while we wait for a reset.
+        self.handle.as_ref().ok_or(Error::DeviceLost)
     }
 }
 
-pub struct TextureManager {
-    textures: HashMap<TextureId, ManagedTexture>,
+/// set in every id we hand out for a native texture, so they never collide with the ones the user resolves.
+const NATIVE_TAG: u64 = 1 << 63;
+
+/// whether a `TextureId::User` id came from [`TextureManager::register_native`].
+pub const fn is_native(id: u64) -> bool {
+    id & NATIVE_TAG != 0
+}
+
+pub struct TextureManager<D: RenderDevice> {
+    textures: HashMap<TextureId, ManagedTexture<D::Texture>>,
+    // textures the user registered with us, they own the contents.
+    // `None` from a reset until they hand us the texture again.
+    native: HashMap<u64, Option<D::Texture>>,
+    next_native_id: u64,
+    // plain white, drawn in place of anything we can't resolve.
+    placeholder: Option<D::Texture>,
 }
 
-impl TextureManager {
+impl<D: RenderDevice> TextureManager<D> {
     pub fn new() -> Self {
         Self {
             textures: HashMap::new(),
+            native: HashMap::new(),
+            next_native_id: 0,
+            placeholder: None,
         }
     }
 }
 
-impl TextureManager {
-    pub fn process_set_deltas(
-        &mut self,
-        dev: &IDirect3DDevice9,
-        delta: &TexturesDelta,
-    ) -> windows::core::Result<()> {
+impl<D: RenderDevice> TextureManager<D> {
+    pub fn process_set_deltas(&mut self, dev: &D, delta: &TexturesDelta) -> Result<()> {
         delta.set.iter().try_for_each(|(tid, delta)| {
             // check if this texture already exists
             if self.textures.contains_key(tid) {
-                if delta.is_whole() {
+                match delta.pos {
                     // update the entire texture
-                    self.update_texture_whole(dev, tid, &delta.image)
-                } else {
+                    None => self.update_texture_whole(dev, tid, delta),
                     // update part of the texture
-                    self.update_texture_area(
-                        dev,
-                        tid,
-                        &delta.image,
-                        delta.pos.expect("unable to extract delta position"),
-                    )
+                    Some(pos) => self.update_texture_area(dev, tid, delta, pos),
                 }
             } else {
                 // create new texture
-                self.create_new_texture(dev, tid, &delta.image)
+                self.create_new_texture(dev, tid, delta)
             }
         })?;
 
         Ok(())
     }
 
-    pub fn process_free_deltas(&mut self, delta: &TexturesDelta) {
-        delta.free.iter().for_each(|tid| {
+    pub fn process_free_deltas(&mut self, free: &[TextureId]) {
+        free.iter().for_each(|tid| {
             self.free(tid);
         });
     }
 
-    pub fn get_by_id(&self, id: TextureId) -> &IDirect3DTexture9 {
-        &self
-            .textures
-            .get(&id)
-            .expect("unable to retrieve texture")
-            .handle
-            .as_ref()
-            .expect("unable to retrieve texture handle")
+    /// the texture and how egui wants it sampled.
+    pub fn get_by_id(&self, id: TextureId) -> Result<(&D::Texture, TextureOptions)> {
+        let texture = self.textures.get(&id).ok_or(Error::MissingTexture(id))?;
+
+        Ok((texture.handle()?, texture.options))
+    }
+
+    pub fn get_native(&self, id: u64) -> Option<&D::Texture> {
+        self.native.get(&id)?.as_ref()
+    }
+
+    pub fn register_native(&mut self, texture: D::Texture) -> TextureId {
+        let id = NATIVE_TAG | self.next_native_id;
+        self.next_native_id += 1;
+
+        self.native.insert(id, Some(texture));
+        TextureId::User(id)
+    }
+
+    pub fn replace_native(&mut self, tid: TextureId, texture: D::Texture) -> Result<()> {
+        match tid {
+            TextureId::User(id) => match self.native.get_mut(&id) {
+                Some(old) => {
+                    *old = Some(texture);
+                    Ok(())
+                }
+                None => Err(Error::UnknownNativeTexture(tid)),
+            },
+            TextureId::Managed(_) => Err(Error::UnknownNativeTexture(tid)),
+        }
+    }
+
+    pub fn free_native(&mut self, tid: TextureId) -> Option<D::Texture> {
+        match tid {
+            TextureId::User(id) => self.native.remove(&id).flatten(),
+            TextureId::Managed(_) => None,
+        }
+    }
+
+    pub fn placeholder(&self) -> Result<&D::Texture> {
+        self.placeholder.as_ref().ok_or(Error::DeviceLost)
+    }
+
+    pub fn ensure_placeholder(&mut self, dev: &D) -> Result<()> {
+        if self.placeholder.is_none() {
+            let white = TextureColor {
+                b: 255,
+                g: 255,
+                r: 255,
+                a: 255,
+            };
+
+            self.placeholder = Some(dev.create_texture(&[white], [1, 1])?);
+        }
+
+        Ok(())
     }
 
     pub fn deallocate_textures(&mut self) {
         self.textures.iter_mut().for_each(|(_tid, texture)| {
             texture.handle = None;
         });
+
+        // the user's textures might be in the default pool as well, the reset fails while anyone holds on to those.
+        // we keep the ids, the textures come back through `replace_native`.
+        self.native.values_mut().for_each(|texture| *texture = None);
+
+        self.placeholder = None;
     }
 
-    pub fn reallocate_textures(&mut self, dev: &IDirect3DDevice9) {
-        self.textures.iter_mut().for_each(|(_tid, texture)| {
-            let handle = new_texture_from_buffer(dev, &texture.pixels, texture.size).expect("text");
+    pub fn reallocate_textures(&mut self, dev: &D) -> Result<()> {
+        self.textures.iter_mut().try_for_each(|(_tid, texture)| {
+            let handle = dev.create_texture(&texture.pixels, texture.size)?;
 
             texture.handle = Some(handle);
-        });
+            Ok::<_, Error>(())
+        })?;
+
+        self.ensure_placeholder(dev)
     }
 }
 
-impl TextureManager {
+impl<D: RenderDevice> TextureManager<D> {
     fn free(&mut self, tid: &TextureId) -> bool {
         self.textures.remove(tid).is_some()
     }
 
-    fn create_new_texture(
-        &mut self,
-        dev: &IDirect3DDevice9,
-        tid: &TextureId,
-        img_data: &ImageData,
-    ) -> windows::core::Result<()> {
+    fn create_new_texture(&mut self, dev: &D, tid: &TextureId, delta: &ImageDelta) -> Result<()> {
+        let img_data = &delta.image;
         let pixels = pixels_from_imagedata(img_data);
         let size = img_data.size();
 
-        let handle = new_texture_from_buffer(dev, &pixels, size)?;
+        let handle = dev.create_texture(&pixels, size)?;
 
         self.textures.insert(
             *tid,
@@ -127,6 +183,7 @@ impl TextureManager {
                 handle: Some(handle),
                 pixels,
                 size,
+                options: delta.options,
             },
         );
 
@@ -135,91 +192,60 @@ impl TextureManager {
 
     fn update_texture_area(
         &mut self,
-        dev: &IDirect3DDevice9,
+        dev: &D,
         tid: &TextureId,
-        img_data: &ImageData,
+        delta: &ImageDelta,
         pos: [usize; 2],
-    ) -> windows::core::Result<()> {
-        let x = pos[0];
-        let y = pos[1];
-        let w = img_data.width();
-        let h = img_data.height();
+    ) -> Result<()> {
+        let img_data = &delta.image;
+        let size = img_data.size();
+
+        if size[0] == 0 || size[1] == 0 {
+            return Ok(());
+        }
 
         let pixels = pixels_from_imagedata(img_data);
 
-        let temp_tex = create_temporary_texture(dev, &pixels, [w, h])?;
-
-        unsafe {
-            let texture = self
-                .textures
-                .get(tid)
-                .expect("unable to get texture to delta patch");
-
-            let src_surface = temp_tex.GetSurfaceLevel(0)?;
-
-            let dst_surface = texture
-                .handle
-                .as_ref()
-                .expect("unable to get texture handle")
-                .GetSurfaceLevel(0)?;
-
-            dev.UpdateSurface(
-                &src_surface,
-                &RECT {
-                    left: 0 as _,
-                    right: w as _,
-                    top: 0 as _,
-                    bottom: h as _,
-                },
-                &dst_surface,
-                &POINT {
-                    x: x as _,
-                    y: y as _,
-                },
-            )?;
+        let texture = self
+            .textures
+            .get_mut(tid)
+            .ok_or(Error::MissingTexture(*tid))?;
+
+        dev.update_texture(texture.handle()?, &pixels, pos, size)?;
+        texture.options = delta.options;
+
+        // keep our copy in sync, we need it to re-create the texture after a reset.
+        for (row, src) in pixels.chunks_exact(size[0]).enumerate() {
+            let start = (pos[1] + row) * texture.size[0] + pos[0];
+            texture.pixels[start..start + size[0]].copy_from_slice(src);
         }
 
         Ok(())
     }
 
-    fn update_texture_whole(
-        &mut self,
-        dev: &IDirect3DDevice9,
-        tid: &TextureId,
-        img_data: &ImageData,
-    ) -> windows::core::Result<()> {
-        let texture = self.textures.get_mut(tid).expect("unable to get texture");
+    fn update_texture_whole(&mut self, dev: &D, tid: &TextureId, delta: &ImageDelta) -> Result<()> {
+        let img_data = &delta.image;
+        let texture = self
+            .textures
+            .get_mut(tid)
+            .ok_or(Error::MissingTexture(*tid))?;
         let size = img_data.size();
 
         let pixels = pixels_from_imagedata(img_data);
 
         if size == texture.size {
             // perfectly normal update operation
-            let temp_tex = create_temporary_texture(dev, &pixels, size)?;
-
-            let handle = texture.handle();
-            unsafe {
-                handle
-                    .AddDirtyRect(&RECT {
-                        left: 0,
-                        top: 0,
-                        right: size[0] as _,
-                        bottom: size[1] as _,
-                    })?;
-                dev.UpdateTexture(
-                    &temp_tex,
-                    handle
-                )?;
-            }
+            dev.update_texture(texture.handle()?, &pixels, [0, 0], size)?;
 
             texture.pixels = pixels;
+            texture.options = delta.options;
         } else {
             // size mismatch, recreate texture
             // free texture
             self.free(tid);
 
             // create a new texture with new data
-            let handle = new_texture_from_buffer(dev, &pixels, size)?;
+            let handle = dev.create_texture(&pixels, size)?;
 
             // insert new texture under same key
             self.textures.insert(
@@ -228,6 +254,7 @@ impl TextureManager {
                     handle: Some(handle),
                     pixels,
                     size,
+                    options: delta.options,
                 },
             );
         }
@@ -266,69 +293,59 @@ fn pixels_from_imagedata(img_data: &ImageData) -> Vec<TextureColor> {
     }
 }
 
-fn create_temporary_texture(
-    dev: &IDirect3DDevice9,
-    buf: &[TextureColor],
-    size: [usize; 2],
-) -> windows::core::Result<IDirect3DTexture9> {
-    unsafe {
-        let mut temp_texture: Option<IDirect3DTexture9> = None;
-
-        dev.CreateTexture(
-            size[0] as _,
-            size[1] as _,
-            1,
-            D3DUSAGE_DYNAMIC as _,
-            D3DFMT_A8R8G8B8,
-            D3DPOOL_SYSTEMMEM,
-            &mut temp_texture,
-            std::ptr::null_mut(),
-        )?;
-
-        let temp_texture = temp_texture.expect("unable to create temporary texture");
-
-        let mut locked_rect = D3DLOCKED_RECT::default();
-
-        temp_texture.LockRect(
-            0,
-            &mut locked_rect,
-            std::ptr::null_mut(),
-            D3DLOCK_DISCARD as u32 | D3DLOCK_READONLY as u32,
-        )?;
-
-        std::slice::from_raw_parts_mut(locked_rect.pBits.cast::<TextureColor>(), size[0] * size[1])
-            .copy_from_slice(buf);
-
-        temp_texture.UnlockRect(0)?;
-
-        Ok(temp_texture)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RecordingDevice;
+
+    #[test]
+    fn native_textures_are_released_on_reset() {
+        let dev = RecordingDevice::new();
+        let mut tex_man = TextureManager::<RecordingDevice>::new();
+
+        let TextureId::User(id) = tex_man.register_native(7) else {
+            unreachable!();
+        };
+        assert_eq!(tex_man.get_native(id), Some(&7));
+
+        tex_man.deallocate_textures();
+        assert_eq!(tex_man.get_native(id), None);
+
+        // the id survives, the texture comes back from the user.
+        tex_man.reallocate_textures(&dev).unwrap();
+        assert_eq!(tex_man.get_native(id), None);
+
+        tex_man.replace_native(TextureId::User(id), 8).unwrap();
+        assert_eq!(tex_man.get_native(id), Some(&8));
+        assert_eq!(tex_man.free_native(TextureId::User(id)), Some(8));
     }
-}
 
-fn new_texture_from_buffer(
-    dev: &IDirect3DDevice9,
-    buf: &[TextureColor],
-    size: [usize; 2],
-) -> windows::core::Result<IDirect3DTexture9> {
-    let temp_tex = create_temporary_texture(dev, buf, size)?;
-    let mut texture: Option<IDirect3DTexture9> = None;
-
-    unsafe {
-        dev.CreateTexture(
-            size[0] as _,
-            size[1] as _,
-            1,
-            D3DUSAGE_DYNAMIC as _,
-            D3DFMT_A8R8G8B8,
-            D3DPOOL_DEFAULT,
-            &mut texture,
-            std::ptr::null_mut(),
-        )?;
-
-        let texture = texture.expect("unable to create texture");
-
-        dev.UpdateTexture(&temp_tex, &texture)?;
-
-        Ok(texture)
+    #[test]
+    fn native_ids_never_collide_with_user_ids() {
+        let mut tex_man = TextureManager::<RecordingDevice>::new();
+
+        let TextureId::User(id) = tex_man.register_native(7) else {
+            unreachable!();
+        };
+        assert!(is_native(id));
+        assert!(!is_native(0));
+
+        assert_eq!(tex_man.get_native(0), None);
+        assert_eq!(tex_man.free_native(TextureId::User(0)), None);
+        assert_eq!(tex_man.get_native(id), Some(&7));
+    }
+
+    #[test]
+    fn replacing_unknown_textures_fails() {
+        let mut tex_man = TextureManager::<RecordingDevice>::new();
+
+        assert!(matches!(
+            tex_man.replace_native(TextureId::User(3), 1),
+            Err(Error::UnknownNativeTexture(TextureId::User(3)))
+        ));
+        assert!(matches!(
+            tex_man.replace_native(TextureId::Managed(0), 1),
+            Err(Error::UnknownNativeTexture(TextureId::Managed(0)))
+        ));
     }
 }
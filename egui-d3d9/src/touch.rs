@@ -0,0 +1,207 @@
+use egui::{Event, Modifiers, PointerButton, Pos2, TouchDeviceId, TouchId, TouchPhase};
+
+/// `MI_WP_SIGNATURE`, what windows marks mouse messages it made up from touch or pen input with.
+const EMULATED_SIGNATURE: u32 = 0xFF51_5700;
+
+/// whether a mouse message was made up from touch or pen input, `extra_info` is from `GetMessageExtraInfo`.
+pub const fn is_emulated_mouse(extra_info: isize) -> bool {
+    extra_info as u32 & 0xFFFF_FF00 == EMULATED_SIGNATURE
+}
+
+/// one finger or pen touching the screen, with its position in points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    pub device: u64,
+    pub id: u64,
+    pub phase: TouchPhase,
+    pub pos: Pos2,
+    /// 0 to 1, if the device knows.
+    pub force: Option<f32>,
+}
+
+/// turns contacts into egui events.
+///
+/// egui only uses `Event::Touch` for gestures like pinching, so the first contact also acts as the mouse.
+#[derive(Default)]
+pub struct TouchState {
+    // device and id of the contact that moves the pointer
+    primary: Option<(u64, u64)>,
+}
+
+impl TouchState {
+    pub fn translate(&mut self, contact: Contact, modifiers: Modifiers) -> Vec<Event> {
+        let mut events = vec![Event::Touch {
+            device_id: TouchDeviceId(contact.device),
+            id: TouchId(contact.id),
+            phase: contact.phase,
+            pos: contact.pos,
+            force: contact.force,
+        }];
+
+        let key = (contact.device, contact.id);
+        if contact.phase == TouchPhase::Start && self.primary.is_none() {
+            self.primary = Some(key);
+        }
+
+        if self.primary != Some(key) {
+            return events;
+        }
+
+        let button = |pressed| Event::PointerButton {
+            pos: contact.pos,
+            button: PointerButton::Primary,
+            pressed,
+            modifiers,
+        };
+
+        match contact.phase {
+            TouchPhase::Start => {
+                events.push(Event::PointerMoved(contact.pos));
+                events.push(button(true));
+            }
+            TouchPhase::Move => events.push(Event::PointerMoved(contact.pos)),
+            // egui has nothing for a cancelled press, a release still beats a button stuck down.
+            TouchPhase::End | TouchPhase::Cancel => {
+                events.push(button(false));
+                events.push(Event::PointerGone);
+                self.primary = None;
+            }
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(id: u64, phase: TouchPhase, x: f32) -> Contact {
+        Contact {
+            device: 1,
+            id,
+            phase,
+            pos: Pos2::new(x, 10.),
+            force: None,
+        }
+    }
+
+    fn touch(id: u64, phase: TouchPhase, x: f32) -> Event {
+        Event::Touch {
+            device_id: TouchDeviceId(1),
+            id: TouchId(id),
+            phase,
+            pos: Pos2::new(x, 10.),
+            force: None,
+        }
+    }
+
+    fn button(pressed: bool, x: f32) -> Event {
+        Event::PointerButton {
+            pos: Pos2::new(x, 10.),
+            button: PointerButton::Primary,
+            pressed,
+            modifiers: Modifiers::NONE,
+        }
+    }
+
+    #[test]
+    fn first_contact_is_the_mouse() {
+        let mut state = TouchState::default();
+        let mut translate = |id, phase, x| state.translate(contact(id, phase, x), Modifiers::NONE);
+
+        assert_eq!(
+            translate(7, TouchPhase::Start, 1.),
+            [
+                touch(7, TouchPhase::Start, 1.),
+                Event::PointerMoved(Pos2::new(1., 10.)),
+                button(true, 1.),
+            ]
+        );
+        assert_eq!(
+            translate(7, TouchPhase::Move, 2.),
+            [
+                touch(7, TouchPhase::Move, 2.),
+                Event::PointerMoved(Pos2::new(2., 10.)),
+            ]
+        );
+        assert_eq!(
+            translate(7, TouchPhase::End, 3.),
+            [
+                touch(7, TouchPhase::End, 3.),
+                button(false, 3.),
+                Event::PointerGone,
+            ]
+        );
+    }
+
+    #[test]
+    fn other_contacts_are_only_touches() {
+        let mut state = TouchState::default();
+        let mut translate = |id, phase, x| state.translate(contact(id, phase, x), Modifiers::NONE);
+
+        translate(1, TouchPhase::Start, 1.);
+        assert_eq!(
+            translate(2, TouchPhase::Start, 5.),
+            [touch(2, TouchPhase::Start, 5.)]
+        );
+        assert_eq!(
+            translate(2, TouchPhase::Move, 6.),
+            [touch(2, TouchPhase::Move, 6.)]
+        );
+
+        // the first one lifting doesn't hand the mouse over.
+        assert_eq!(translate(1, TouchPhase::End, 1.).len(), 3);
+        assert_eq!(
+            translate(2, TouchPhase::End, 6.),
+            [touch(2, TouchPhase::End, 6.)]
+        );
+
+        // the next new contact does get it.
+        assert_eq!(translate(3, TouchPhase::Start, 9.).len(), 3);
+    }
+
+    #[test]
+    fn cancel_releases_the_button() {
+        let mut state = TouchState::default();
+
+        state.translate(contact(1, TouchPhase::Start, 1.), Modifiers::NONE);
+        assert_eq!(
+            state.translate(contact(1, TouchPhase::Cancel, 1.), Modifiers::CTRL),
+            [
+                touch(1, TouchPhase::Cancel, 1.),
+                Event::PointerButton {
+                    pos: Pos2::new(1., 10.),
+                    button: PointerButton::Primary,
+                    pressed: false,
+                    modifiers: Modifiers::CTRL,
+                },
+                Event::PointerGone,
+            ]
+        );
+        assert_eq!(state.primary, None);
+    }
+
+    #[test]
+    fn same_id_on_another_device() {
+        let mut state = TouchState::default();
+
+        state.translate(contact(1, TouchPhase::Start, 1.), Modifiers::NONE);
+        let pen = Contact {
+            device: 2,
+            ..contact(1, TouchPhase::Move, 4.)
+        };
+        assert_eq!(state.translate(pen, Modifiers::NONE).len(), 1);
+    }
+
+    #[test]
+    fn emulated_mouse_messages() {
+        // touch sets 0x80 in the low byte, pen doesn't.
+        assert!(is_emulated_mouse(0xFF51_5780_u32 as isize));
+        assert!(is_emulated_mouse(0xFF51_5700_u32 as isize));
+        assert!(!is_emulated_mouse(0));
+        assert!(!is_emulated_mouse(0xFF51_5600_u32 as isize));
+        // only the low 32 bits count.
+        assert!(is_emulated_mouse(0x1_FF51_5780_i64 as isize));
+    }
+}
@@ -0,0 +1,225 @@
+use egui::{Event, Modifiers, MouseWheelUnit, Vec2};
+
+/// one notch of a regular wheel, `WHEEL_DELTA`. touchpads and free-spinning wheels send fractions of it.
+const NOTCH: f32 = 120.;
+
+/// `WHEEL_PAGESCROLL`, what windows reports as lines when a notch should scroll a whole page.
+pub const PAGE_SCROLL: u32 = u32::MAX;
+
+/// how the mouse wheel scrolls and zooms.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WheelConfig {
+    /// multiplies every scroll, on top of the lines per notch.
+    pub scroll_sensitivity: f32,
+    /// lines one notch scrolls, vertically and horizontally.
+    /// `None` goes by what the user set in windows' mouse settings.
+    pub lines_per_notch: Option<u32>,
+    /// how much one notch zooms while ctrl is held, `1.1` zooms in by 10%.
+    pub zoom_per_notch: f32,
+}
+
+impl Default for WheelConfig {
+    fn default() -> Self {
+        Self {
+            scroll_sensitivity: 1.,
+            lines_per_notch: None,
+            zoom_per_notch: 1.1,
+        }
+    }
+}
+
+/// adds up wheel messages until egui asks for input.
+///
+/// a touchpad sends lots of tiny deltas per frame, egui gets one event for all of them and nothing is rounded away.
+#[derive(Default)]
+pub struct WheelState {
+    // raw deltas, in fractions of a notch
+    scroll: Vec2,
+    zoom: f32,
+    // held during the last scroll
+    modifiers: Modifiers,
+}
+
+impl WheelState {
+    /// add a delta from `WM_MOUSEWHEEL` or `WM_MOUSEHWHEEL`. returns whether it zooms.
+    pub fn push(&mut self, delta: i16, horizontal: bool, modifiers: Modifiers) -> bool {
+        let delta = f32::from(delta);
+
+        if modifiers.ctrl {
+            // tilting doesn't zoom.
+            if !horizontal {
+                self.zoom += delta;
+            }
+            return true;
+        }
+
+        if horizontal {
+            // windows has right positive, egui moves the content with the wheel.
+            self.scroll.x -= delta;
+        } else {
+            self.scroll.y += delta;
+        }
+        self.modifiers = modifiers;
+
+        false
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scroll == Vec2::ZERO && self.zoom == 0.
+    }
+
+    /// what egui should see for everything added since the last call.
+    ///
+    /// `lines` is how many lines (x: characters) a notch scrolls, [`PAGE_SCROLL`] for whole pages.
+    /// [`WheelConfig::lines_per_notch`] takes precedence if set.
+    pub fn take(&mut self, config: &WheelConfig, lines: [u32; 2]) -> Vec<Event> {
+        let mut events = vec![];
+
+        if self.zoom != 0. {
+            let notches = self.zoom / NOTCH;
+            events.push(Event::Zoom(config.zoom_per_notch.powf(notches)));
+            self.zoom = 0.;
+        }
+
+        if self.scroll != Vec2::ZERO {
+            let lines = config.lines_per_notch.map_or(lines, |lines| [lines; 2]);
+            let notches = self.scroll / NOTCH * config.scroll_sensitivity;
+
+            // a page for one axis means pages for both, egui has one unit per event.
+            let (delta, unit) = if lines.contains(&PAGE_SCROLL) {
+                (notches, MouseWheelUnit::Page)
+            } else {
+                (
+                    notches * Vec2::new(lines[0] as f32, lines[1] as f32),
+                    MouseWheelUnit::Line,
+                )
+            };
+
+            events.push(Event::MouseWheel {
+                unit,
+                delta,
+                modifiers: self.modifiers,
+            });
+            self.scroll = Vec2::ZERO;
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINES: [u32; 2] = [3, 3];
+
+    fn wheel(delta: Vec2, unit: MouseWheelUnit, modifiers: Modifiers) -> Event {
+        Event::MouseWheel {
+            unit,
+            delta,
+            modifiers,
+        }
+    }
+
+    #[test]
+    fn fractions_of_a_notch_add_up() {
+        let mut state = WheelState::default();
+        let config = WheelConfig::default();
+
+        // a hi-res wheel or touchpad, four steps of a quarter notch.
+        for _ in 0..4 {
+            assert!(!state.push(30, false, Modifiers::NONE));
+        }
+        assert_eq!(
+            state.take(&config, LINES),
+            [wheel(
+                Vec2::new(0., 3.),
+                MouseWheelUnit::Line,
+                Modifiers::NONE
+            )]
+        );
+        assert!(state.is_empty());
+        assert_eq!(state.take(&config, LINES), []);
+
+        // nothing is rounded away, even if it never makes up a whole notch.
+        state.push(15, false, Modifiers::NONE);
+        assert_eq!(
+            state.take(&config, LINES),
+            [wheel(
+                Vec2::new(0., 0.375),
+                MouseWheelUnit::Line,
+                Modifiers::NONE
+            )]
+        );
+
+        // back and forth cancels out.
+        state.push(-120, false, Modifiers::NONE);
+        state.push(120, false, Modifiers::NONE);
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn horizontal_is_flipped() {
+        let mut state = WheelState::default();
+
+        // tilted right.
+        state.push(120, true, Modifiers::SHIFT);
+        assert_eq!(
+            state.take(&WheelConfig::default(), [2, 3]),
+            [wheel(
+                Vec2::new(-2., 0.),
+                MouseWheelUnit::Line,
+                Modifiers::SHIFT
+            )]
+        );
+    }
+
+    #[test]
+    fn lines_and_pages() {
+        let mut state = WheelState::default();
+        let config = WheelConfig {
+            lines_per_notch: Some(5),
+            scroll_sensitivity: 2.,
+            ..WheelConfig::default()
+        };
+
+        state.push(-120, false, Modifiers::NONE);
+        assert_eq!(
+            state.take(&config, LINES),
+            [wheel(
+                Vec2::new(0., -10.),
+                MouseWheelUnit::Line,
+                Modifiers::NONE
+            )]
+        );
+
+        state.push(60, false, Modifiers::NONE);
+        assert_eq!(
+            state.take(&WheelConfig::default(), [3, PAGE_SCROLL]),
+            [wheel(
+                Vec2::new(0., 0.5),
+                MouseWheelUnit::Page,
+                Modifiers::NONE
+            )]
+        );
+    }
+
+    #[test]
+    fn ctrl_zooms() {
+        let mut state = WheelState::default();
+        let config = WheelConfig {
+            zoom_per_notch: 2.,
+            ..WheelConfig::default()
+        };
+
+        assert!(state.push(120, false, Modifiers::CTRL));
+        assert!(state.push(120, false, Modifiers::CTRL));
+        // tilting with ctrl held does nothing.
+        assert!(state.push(120, true, Modifiers::CTRL));
+        assert_eq!(state.take(&config, LINES), [Event::Zoom(4.)]);
+
+        state.push(-60, false, Modifiers::CTRL);
+        assert_eq!(state.take(&config, LINES), [Event::Zoom(2_f32.powf(-0.5))]);
+        assert!(state.is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+//! renders a few frames on the cpu and compares them against the images in `tests/golden`.
+//!
+//! after a change that is supposed to look different, re-write the images with
+//! `EGUI_D3D9_BLESS=1 cargo test --test golden`, look at them, and commit them.
+//! a failing test leaves what it rendered next to the golden image as `*.actual.pam`.
+
+use egui::{
+    pos2, vec2, Color32, ColorImage, Context, FontId, LayerId, RawInput, Rect, TextureOptions,
+    ViewportId,
+};
+use egui_d3d9::{compare_golden, Renderer, SoftwareDevice};
+
+const SCREEN: [u32; 2] = [160, 80];
+
+/// rounding in the rasterizer and the font renderer, nothing you could see.
+const TOLERANCE: u8 = 2;
+
+fn render(pixels_per_point: f32, ui: impl FnMut(&Context)) -> egui::ColorImage {
+    let dev = SoftwareDevice::new();
+    dev.clear(
+        [SCREEN[0] as usize, SCREEN[1] as usize],
+        Color32::from_gray(32),
+    );
+
+    let mut renderer = Renderer::new(&dev, (), 1024, 1024, TextureOptions::LINEAR).unwrap();
+
+    let mut input = RawInput {
+        screen_rect: Some(Rect::from_min_size(
+            pos2(0., 0.),
+            vec2(SCREEN[0] as f32, SCREEN[1] as f32) / pixels_per_point,
+        )),
+        ..RawInput::default()
+    };
+    input
+        .viewports
+        .entry(ViewportId::ROOT)
+        .or_default()
+        .native_pixels_per_point = Some(pixels_per_point);
+
+    let ctx = Context::default();
+    renderer.run(&dev, &ctx, input, SCREEN, ui).unwrap();
+    renderer.draw(&dev, SCREEN, |_| None).unwrap();
+
+    dev.image()
+}
+
+fn golden(name: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name)
+        .with_extension("pam")
+}
+
+#[test]
+fn label_and_rects() {
+    let image = render(2., |ctx| {
+        let painter = ctx.layer_painter(LayerId::background());
+
+        painter.rect_filled(
+            Rect::from_min_size(pos2(4., 4.), vec2(20., 12.)),
+            0.,
+            Color32::RED,
+        );
+        painter.rect_filled(
+            Rect::from_min_size(pos2(28., 4.), vec2(20., 12.)),
+            0.,
+            Color32::from_rgb(0, 255, 0),
+        );
+        painter.rect_filled(
+            Rect::from_min_size(pos2(52., 4.), vec2(20., 12.)),
+            4.,
+            Color32::BLUE,
+        );
+        // half transparent, blends with what's underneath.
+        painter.rect_filled(
+            Rect::from_min_size(pos2(16., 10.), vec2(40., 10.)),
+            0.,
+            Color32::from_white_alpha(128),
+        );
+
+        painter.text(
+            pos2(4., 24.),
+            egui::Align2::LEFT_TOP,
+            "golden",
+            FontId::proportional(12.),
+            Color32::WHITE,
+        );
+    });
+
+    // solid fills land on whole pixels at this scale, they have to be exact.
+    assert_eq!(image.pixels[(10 * 160) + 10], Color32::RED);
+    assert_eq!(image.pixels[(10 * 160) + 60], Color32::from_rgb(0, 255, 0));
+
+    compare_golden(&image, golden("label_and_rects"), TOLERANCE).unwrap();
+}
+
+#[test]
+fn textures_keep_their_own_filter() {
+    // one black and one white texel, stretched over 40 pixels.
+    let stripes = |options: TextureOptions| {
+        render(1., move |ctx| {
+            let image =
+                ColorImage::from_rgba_unmultiplied([2, 1], &[0, 0, 0, 255, 255, 255, 255, 255]);
+            let texture = ctx.load_texture("stripes", image, options);
+
+            ctx.layer_painter(LayerId::background()).image(
+                texture.id(),
+                Rect::from_min_size(pos2(0., 0.), vec2(40., 10.)),
+                Rect::from_min_max(pos2(0., 0.), pos2(1., 1.)),
+                Color32::WHITE,
+            );
+        })
+    };
+    let greys = |image: &ColorImage| {
+        image.pixels[..40]
+            .iter()
+            .filter(|pixel| pixel.r() != 0 && pixel.r() != 255)
+            .count()
+    };
+
+    // the renderer's default is linear, egui's say wins.
+    assert_eq!(greys(&stripes(TextureOptions::NEAREST)), 0);
+    assert!(greys(&stripes(TextureOptions::LINEAR)) > 0);
+}
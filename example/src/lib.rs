@@ -7,10 +7,10 @@ use egui::{
     ImageSource, Key, Modifiers, Pos2, Rect, RichText, ScrollArea, Slider, Stroke, TextureId, Vec2,
     Widget,
 };
-use egui_d3d9::EguiDx9;
+use egui_d3d9::{EguiDx9, Error, InputSink, UIHandler};
 use std::{
     intrinsics::transmute,
-    sync::{Arc, Once},
+    sync::{Arc, Once, OnceLock},
     time::Duration,
 };
 use windows::{
@@ -27,7 +27,8 @@ use windows::{
         },
         System::{Console::AllocConsole, LibraryLoader::GetModuleHandleA},
         UI::WindowsAndMessaging::{
-            CallWindowProcW, FindWindowA, SetWindowLongPtrA, GWLP_WNDPROC, WNDPROC,
+            CallWindowProcW, DefWindowProcW, FindWindowA, SetWindowLongPtrA, GWLP_WNDPROC,
+            WM_INPUT, WNDPROC,
         },
     },
 };
@@ -41,8 +42,10 @@ extern "stdcall" fn DllMain(hinst: usize, reason: u32, _reserved: *mut ()) -> i3
     1
 }
 
-static mut APP: Option<EguiDx9<i32>> = None;
-static mut OLD_WND_PROC: Option<WNDPROC> = None;
+static mut APP: Option<EguiDx9<Overlay>> = None;
+// the window procedure can run on another thread than present.
+static INPUT: OnceLock<InputSink> = OnceLock::new();
+static mut OLD_WND_PROC: WNDPROC = None;
 
 static_detour! {
     static PresentHook: unsafe extern "stdcall" fn(IDirect3DDevice9, *const RECT, *const RECT, HWND, *const RGNDATA) -> HRESULT;
@@ -73,19 +76,36 @@ fn hk_present(
         static INIT: Once = Once::new();
 
         INIT.call_once(|| {
-            // let window = FindWindowA(s!("Valve001"), PCSTR(std::ptr::null()));
-            let window = FindWindowA(s!("Valve001"), PCSTR(std::ptr::null()));
-
-            APP = Some(EguiDx9::init(&dev, window, ui, 0, true));
-
-            OLD_WND_PROC = Some(transmute(SetWindowLongPtrA(
+            let Ok(window) = FindWindowA(s!("Valve001"), PCSTR(std::ptr::null())) else {
+                eprintln!("couldn't find the game's window");
+                return;
+            };
+
+            let app = match EguiDx9::init(&dev, window, Overlay::default(), true) {
+                Ok(app) => app,
+                Err(err) => {
+                    eprintln!("couldn't set up egui: {err}");
+                    return;
+                }
+            };
+
+            let _ = INPUT.set(app.input_sink());
+            APP = Some(app);
+
+            OLD_WND_PROC = transmute(SetWindowLongPtrA(
                 window,
                 GWLP_WNDPROC,
                 hk_wnd_proc as usize as _,
-            )));
+            ));
         });
 
-        APP.as_mut().unwrap().present(&dev);
+        if let Some(app) = APP.as_mut() {
+            match app.present(&dev) {
+                // the game resets the device soon, we'll draw again after that.
+                Ok(()) | Err(Error::DeviceLost) => {}
+                Err(err) => eprintln!("couldn't draw egui: {err}"),
+            }
+        }
 
         PresentHook.call(dev, source_rect, dest_rect, window, rgn_data)
     }
@@ -96,7 +116,9 @@ fn hk_reset(
     presentation_parameters: *const D3DPRESENT_PARAMETERS,
 ) -> HRESULT {
     unsafe {
-        APP.as_mut().unwrap().pre_reset();
+        if let Some(app) = APP.as_mut() {
+            app.pre_reset();
+        }
 
         ResetHook.call(dev, presentation_parameters)
     }
@@ -108,9 +130,31 @@ unsafe extern "stdcall" fn hk_wnd_proc(
     wparam: WPARAM,
     lparam: LPARAM,
 ) -> LRESULT {
-    APP.as_mut().unwrap().wnd_proc(msg, wparam, lparam);
+    let consumed = INPUT
+        .get()
+        .is_some_and(|input| input.wnd_proc(msg, wparam, lparam).is_consumed());
+
+    if consumed {
+        // the game doesn't see it, but windows still has to clean up after raw input.
+        if msg == WM_INPUT {
+            return DefWindowProcW(hwnd, msg, wparam, lparam);
+        }
 
-    CallWindowProcW(OLD_WND_PROC.unwrap(), hwnd, msg, wparam, lparam)
+        return LRESULT(1);
+    }
+
+    CallWindowProcW(OLD_WND_PROC, hwnd, msg, wparam, lparam)
+}
+
+#[derive(Default)]
+struct Overlay {
+    clicks: i32,
+}
+
+impl UIHandler for Overlay {
+    fn ui(&mut self, ctx: &Context) {
+        ui(ctx, &mut self.clicks);
+    }
 }
 
 // most of this code is ported over from sy1ntexx's d3d11 implementation.
@@ -181,7 +225,7 @@ fn ui(ctx: &Context, i: &mut i32) {
             ));
 
             if ui.input(|input| {
-                input.modifiers.matches(Modifiers::CTRL) && input.key_pressed(Key::R)
+                input.modifiers.matches_logically(Modifiers::CTRL) && input.key_pressed(Key::R)
             }) {
                 println!("Pressed");
             }
@@ -230,7 +274,7 @@ fn ui(ctx: &Context, i: &mut i32) {
             },
             10.0,
             Color32::from_rgba_premultiplied(255, 0, 0, 150),
-            Stroke::none(),
+            Stroke::NONE,
         );
 
         // this is supposed to be color channel testing to identify if any channels have been misplaced
@@ -238,21 +282,21 @@ fn ui(ctx: &Context, i: &mut i32) {
             Pos2::new(350.0, 350.0),
             35.0,
             Color32::from_rgba_premultiplied(255, 0, 0, 0),
-            Stroke::none(),
+            Stroke::NONE,
         );
 
         ctx.debug_painter().circle(
             Pos2::new(450.0, 350.0),
             35.0,
             Color32::from_rgba_premultiplied(0, 255, 0, 0),
-            Stroke::none(),
+            Stroke::NONE,
         );
 
         ctx.debug_painter().circle(
             Pos2::new(550.0, 350.0),
             35.0,
             Color32::from_rgba_premultiplied(0, 0, 255, 0),
-            Stroke::none(),
+            Stroke::NONE,
         );
 
         ctx.debug_painter().circle(
@@ -266,12 +310,12 @@ fn ui(ctx: &Context, i: &mut i32) {
 
 unsafe fn main_thread(_hinst: usize) {
     unsafe {
-        AllocConsole();
+        let _ = AllocConsole();
     }
 
     unsafe {
         // for valve games
-        if FindWindowA(s!("Valve001"), PCSTR(std::ptr::null())).0 != 0 {
+        if FindWindowA(s!("Valve001"), PCSTR(std::ptr::null())).is_ok() {
             while GetModuleHandleA(s!("serverbrowser.dll")).is_err() {
                 std::thread::sleep(Duration::new(0, 100_000_000));
             }